@@ -1,5 +1,147 @@
+mod client;
+mod commits;
+mod graphql;
+mod issues;
 mod pull_requests;
 mod releases;
+mod repositories;
+mod reviews;
+mod search;
+mod workflows;
 
+use std::future::Future;
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, NaiveDate, NaiveDateTime, SecondsFormat, TimeZone, Utc};
+use grafana_plugin_sdk::{PageToken, RetryableError};
+use octocrab::Page;
+use serde::Deserialize;
+
+use client::GitHub;
+
+pub use client::Clients;
+pub use commits::{Commit, CommitTable};
+pub use issues::{Issue, IssueTable};
 pub use pull_requests::{PullRequest, PullRequestTable};
 pub use releases::{Release, ReleaseTable};
+pub use repositories::{Repos, Repository, RepositoryTable};
+pub use reviews::{PullRequestReview, PullRequestReviewTable};
+pub use workflows::{WorkflowJob, WorkflowJobTable, WorkflowRun, WorkflowRunTable};
+
+/// The messages github answers server errors with, as octocrab doesn't keep their status.
+const SERVER_ERRORS: &[&str] = &[
+  "Server Error",
+  "Bad Gateway",
+  "Service Unavailable",
+  "Gateway Timeout",
+  "couldn't respond to your request in time",
+];
+
+/// Awaits a GitHub API call, marking rate limits, server errors and connection failures as
+/// retryable.
+async fn call<T>(github: &GitHub, f: impl Future<Output = octocrab::Result<T>>) -> Result<T> {
+  let e = match f.await {
+    Ok(value) => return Ok(value),
+    Err(e) => e,
+  };
+
+  match &e {
+    octocrab::Error::GitHub { source, .. } if source.message.contains("rate limit") => {
+      let retry_after = rate_limit_reset(github).await;
+      Err(RetryableError::rate_limited(source.message.clone(), retry_after).into())
+    }
+    octocrab::Error::GitHub { source, .. }
+      if SERVER_ERRORS.iter().any(|m| source.message.contains(m)) =>
+    {
+      Err(RetryableError::unavailable(source.message.clone(), None).into())
+    }
+    octocrab::Error::Http { .. } => Err(RetryableError::unavailable(e.to_string(), None).into()),
+    _ => Err(e.into()),
+  }
+}
+
+/// How long until the exhausted rate limit resets, which octocrab doesn't expose from the
+/// response headers.
+async fn rate_limit_reset(github: &GitHub) -> Option<Duration> {
+  let limits: serde_json::Value = github.get("/rate_limit", None::<&()>).await.ok()?;
+  let rate = &limits["rate"];
+  if rate["remaining"].as_i64()? > 0 {
+    return None;
+  }
+
+  let reset = rate["reset"].as_i64()? - Utc::now().timestamp();
+  Some(Duration::from_secs(reset.max(0) as u64))
+}
+
+/// The number of a page of a github list endpoint, which are all numbered from 1.
+fn page_number(page: &PageToken) -> Result<u32> {
+  match page {
+    PageToken::First => Ok(1),
+    PageToken::Number(n) => Ok(*n),
+    page => Err(anyhow!("github pages are numbered, not {:?}", page)),
+  }
+}
+
+/// The next page and the number of pages, from the `Link` header of a github list response.
+fn next_page<T>(page: &Page<T>) -> (Option<PageToken>, Option<u32>) {
+  (
+    page_param(page.next.as_ref()).map(PageToken::Number),
+    page_param(page.last.as_ref()),
+  )
+}
+
+fn page_param(url: Option<&impl AsRef<str>>) -> Option<u32> {
+  let query = url?.as_ref().split('?').nth(1)?;
+  query
+    .split('&')
+    .find_map(|p| p.strip_prefix("page="))?
+    .parse()
+    .ok()
+}
+
+/// A user or organization, as github embeds them in other objects.
+#[derive(Deserialize, Debug)]
+struct Account {
+  login: String,
+}
+
+/// A label of an issue or pull request.
+#[derive(Deserialize, Debug)]
+struct Label {
+  name: String,
+}
+
+/// Parses a timestamp pushed down in a filter, e.g. `created_at >= '2021-06-01'`.
+fn parse_time(value: &str) -> Result<DateTime<Utc>> {
+  if let Ok(t) = DateTime::parse_from_rfc3339(value) {
+    return Ok(t.with_timezone(&Utc));
+  }
+
+  for format in &["%Y-%m-%d %H:%M:%S", "%Y-%m-%dT%H:%M:%S"] {
+    if let Ok(t) = NaiveDateTime::parse_from_str(value, format) {
+      return Ok(DateTime::from_utc(t, Utc));
+    }
+  }
+
+  if let Ok(d) = NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+    return Ok(DateTime::from_utc(d.and_hms(0, 0, 0), Utc));
+  }
+
+  // timestamp literals are pushed down as nanoseconds
+  match value.parse() {
+    Ok(nanos) => Ok(Utc.timestamp_nanos(nanos)),
+    Err(_) => Err(anyhow!("invalid timestamp {:?}", value)),
+  }
+}
+
+/// The value of a search qualifier for a time range, e.g. `created:>=2021-06-01T00:00:00Z`.
+fn time_qualifier(since: Option<DateTime<Utc>>, until: Option<DateTime<Utc>>) -> Option<String> {
+  let format = |t: DateTime<Utc>| t.to_rfc3339_opts(SecondsFormat::Secs, true);
+  match (since, until) {
+    (Some(since), Some(until)) => Some(format!("{}..{}", format(since), format(until))),
+    (Some(since), None) => Some(format!(">={}", format(since))),
+    (None, Some(until)) => Some(format!("<={}", format(until))),
+    (None, None) => None,
+  }
+}
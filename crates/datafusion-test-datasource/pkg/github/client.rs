@@ -0,0 +1,595 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, Utc};
+use grafana_plugin_sdk::proto::pluginv2::PluginContext;
+use grafana_plugin_sdk::{
+  current_plugin_context, HealthCheck, RateLimitStats, RateLimiter, Reporter, RetryPolicy,
+};
+use jsonwebtoken::{Algorithm, EncodingKey, Header};
+use octocrab::{FromResponse, Octocrab, OctocrabBuilder};
+use reqwest::header::{HeaderMap, HeaderValue, ACCEPT, AUTHORIZATION};
+use reqwest::Certificate;
+use serde::{Deserialize, Serialize};
+
+use super::call;
+use super::graphql::{CostStats, GraphQlCost};
+
+// the keys of the secure json data of a datasource instance
+const ACCESS_TOKEN: &str = "accessToken";
+const PRIVATE_KEY: &str = "privateKey";
+const CA_CERTIFICATE: &str = "tlsCACert";
+
+/// The json data of a datasource instance.
+#[derive(Deserialize, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+struct Settings {
+  app_id: Option<String>,
+  installation_id: Option<String>,
+  #[serde(default)]
+  api: Api,
+  #[serde(default)]
+  text_search: bool,
+}
+
+impl Settings {
+  fn from_context(context: Option<&PluginContext>) -> Result<Self> {
+    match context.and_then(|c| c.data_source_instance_settings.as_ref()) {
+      Some(i) if !i.json_data.is_empty() => Ok(serde_json::from_slice(&i.json_data)?),
+      _ => Ok(Settings::default()),
+    }
+  }
+}
+
+/// Which github api the tables are fetched from.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Api {
+  Rest,
+  /// Fetches only the fields of the columns a query reads, and the reviews of a page of pull
+  /// requests in the same query. Tables that GraphQL doesn't serve use the rest api either way.
+  GraphQl,
+}
+
+impl Default for Api {
+  fn default() -> Self {
+    Api::Rest
+  }
+}
+
+/// How a datasource instance authenticates with github.
+#[derive(Clone, PartialEq)]
+enum Credentials {
+  /// Public repositories only, with at most 60 requests an hour.
+  Anonymous,
+  /// A personal access token.
+  Token(String),
+  /// An installation of a github app.
+  App {
+    app_id: u64,
+    installation_id: u64,
+    private_key: String,
+  },
+}
+
+impl Credentials {
+  fn from_context(context: Option<&PluginContext>) -> Result<Self> {
+    let instance = match context.and_then(|c| c.data_source_instance_settings.as_ref()) {
+      Some(i) => i,
+      None => return Ok(Credentials::Anonymous),
+    };
+    let secret = |key: &str| {
+      instance
+        .decrypted_secure_json_data
+        .get(key)
+        .filter(|s| !s.is_empty())
+        .cloned()
+    };
+
+    if let Some(token) = secret(ACCESS_TOKEN) {
+      return Ok(Credentials::Token(token));
+    }
+
+    let private_key = match secret(PRIVATE_KEY) {
+      Some(k) => k,
+      None => return Ok(Credentials::Anonymous),
+    };
+
+    let settings = Settings::from_context(context)?;
+    let id = |name: &str, value: Option<String>| -> Result<u64> {
+      let value = value.ok_or_else(|| anyhow!("a github app needs an {}", name))?;
+      value
+        .trim()
+        .parse()
+        .map_err(|_| anyhow!("invalid {} {:?}", name, value))
+    };
+
+    Ok(Credentials::App {
+      app_id: id("app id", settings.app_id)?,
+      installation_id: id("installation id", settings.installation_id)?,
+      private_key,
+    })
+  }
+}
+
+/// Where and how a datasource instance connects to github.
+#[derive(Clone, PartialEq)]
+struct Connection {
+  /// The api of a github enterprise server, instead of api.github.com.
+  api_url: Option<String>,
+  credentials: Credentials,
+  api: Api,
+  text_search: bool,
+  /// The PEM encoded certificates of a private CA that signed the certificate of the server.
+  ca_certificate: Option<String>,
+}
+
+impl Connection {
+  fn from_context(context: Option<&PluginContext>) -> Result<Self> {
+    let instance = context.and_then(|c| c.data_source_instance_settings.as_ref());
+    let api_url = instance.and_then(|i| api_url(&i.url));
+    let ca_certificate = instance
+      .and_then(|i| i.decrypted_secure_json_data.get(CA_CERTIFICATE))
+      .filter(|c| !c.trim().is_empty())
+      .cloned();
+    let credentials = Credentials::from_context(context)?;
+    let settings = Settings::from_context(context)?;
+    let api = settings.api;
+
+    // github only answers GraphQL queries with credentials
+    if api == Api::GraphQl && credentials == Credentials::Anonymous {
+      return Err(anyhow!(
+        "the github graphql api needs an access token or a github app"
+      ));
+    }
+
+    Ok(Connection {
+      api_url,
+      credentials,
+      api,
+      text_search: settings.text_search,
+      ca_certificate,
+    })
+  }
+
+  /// The rest api, which row urls point into.
+  fn rest_url(&self) -> String {
+    self
+      .api_url
+      .clone()
+      .unwrap_or_else(|| "https://api.github.com/".to_owned())
+  }
+
+  /// The GraphQL endpoint, which servers serve under `/api/graphql` next to `/api/v3`.
+  fn graphql_url(&self) -> String {
+    match &self.api_url {
+      Some(url) => match url.strip_suffix("/v3/") {
+        Some(api) => format!("{}/graphql", api),
+        None => format!("{}graphql", url),
+      },
+      None => "https://api.github.com/graphql".to_owned(),
+    }
+  }
+
+  fn builder(&self) -> Result<OctocrabBuilder> {
+    let builder = Octocrab::builder();
+    match &self.api_url {
+      Some(url) => Ok(builder.base_url(url.as_str())?),
+      None => Ok(builder),
+    }
+  }
+
+  /// The client that requests are sent with instead of octocrab's, which can't be given
+  /// certificates to trust, if the instance has a CA certificate.
+  ///
+  /// It sends the headers octocrab would, with `token` as the credentials.
+  fn http(&self, token: Option<&str>) -> Result<Option<reqwest::Client>> {
+    let pem = match &self.ca_certificate {
+      Some(pem) => pem,
+      None => return Ok(None),
+    };
+
+    let mut headers = HeaderMap::new();
+    headers.insert(
+      ACCEPT,
+      HeaderValue::from_static("application/vnd.github.v3+json"),
+    );
+    if let Some(token) = token {
+      headers.insert(
+        AUTHORIZATION,
+        HeaderValue::from_str(&format!("Bearer {}", token))?,
+      );
+    }
+
+    let mut builder = reqwest::Client::builder()
+      .user_agent("octocrab")
+      .default_headers(headers);
+    for certificate in ca_certificates(pem)? {
+      builder = builder.add_root_certificate(certificate);
+    }
+    Ok(Some(builder.build()?))
+  }
+
+  /// A client authenticated with the credentials, the client its requests are sent with, and
+  /// when its token expires.
+  async fn connect(
+    &self,
+  ) -> Result<(
+    Arc<Octocrab>,
+    Option<reqwest::Client>,
+    Option<DateTime<Utc>>,
+  )> {
+    match &self.credentials {
+      Credentials::Anonymous if self.api_url.is_none() => {
+        Ok((octocrab::instance(), self.http(None)?, None))
+      }
+      Credentials::Anonymous => Ok((Arc::new(self.builder()?.build()?), self.http(None)?, None)),
+      Credentials::Token(token) => {
+        let octocrab = self.builder()?.personal_token(token.clone()).build()?;
+        Ok((Arc::new(octocrab), self.http(Some(token))?, None))
+      }
+      Credentials::App {
+        app_id,
+        installation_id,
+        private_key,
+      } => {
+        let jwt = app_jwt(*app_id, private_key)?;
+        let app = self.builder()?.personal_token(jwt.clone()).build()?;
+        let route = format!("/app/installations/{}/access_tokens", installation_id);
+        let token: InstallationToken =
+          post(&app, self.http(Some(&jwt))?.as_ref(), &route, None::<&()>)
+            .await
+            .map_err(|e| anyhow!("failed to authenticate github app installation: {}", e))?;
+
+        let http = self.http(Some(&token.token))?;
+        let octocrab = self.builder()?.personal_token(token.token).build()?;
+        Ok((Arc::new(octocrab), http, Some(token.expires_at)))
+      }
+    }
+  }
+}
+
+/// The certificates of a PEM bundle, which reqwest reads one at a time.
+fn ca_certificates(pem: &str) -> Result<Vec<Certificate>> {
+  let certificates = pem
+    .split_inclusive("-----END CERTIFICATE-----")
+    .filter(|c| c.contains("-----BEGIN CERTIFICATE-----"))
+    .map(|c| {
+      Certificate::from_pem(c.trim().as_bytes())
+        .map_err(|e| anyhow!("invalid CA certificate: {}", e))
+    })
+    .collect::<Result<Vec<_>>>()?;
+
+  match certificates.is_empty() {
+    true => Err(anyhow!(
+      "invalid CA certificate: no PEM encoded certificate"
+    )),
+    false => Ok(certificates),
+  }
+}
+
+/// Gets a route of the rest api, with `http` instead of octocrab's client if it's set.
+async fn get<R, P>(
+  octocrab: &Octocrab,
+  http: Option<&reqwest::Client>,
+  route: &str,
+  parameters: Option<&P>,
+) -> octocrab::Result<R>
+where
+  R: FromResponse,
+  P: Serialize + ?Sized,
+{
+  let http = match http {
+    Some(http) => http,
+    None => return octocrab.get(route, parameters).await,
+  };
+
+  let mut request = http.get(octocrab.absolute_url(route)?);
+  if let Some(parameters) = parameters {
+    request = request.query(parameters);
+  }
+  let response = octocrab::map_github_error(octocrab.execute(request).await?).await?;
+  R::from_response(response).await
+}
+
+/// Posts to a route of the rest api, or the GraphQL endpoint, with `http` instead of octocrab's
+/// client if it's set.
+async fn post<R, B>(
+  octocrab: &Octocrab,
+  http: Option<&reqwest::Client>,
+  route: &str,
+  body: Option<&B>,
+) -> octocrab::Result<R>
+where
+  R: FromResponse,
+  B: Serialize + ?Sized,
+{
+  let http = match http {
+    Some(http) => http,
+    None => return octocrab.post(route, body).await,
+  };
+
+  let mut request = http.post(octocrab.absolute_url(route)?);
+  if let Some(body) = body {
+    request = request.json(body);
+  }
+  let response = octocrab::map_github_error(octocrab.execute(request).await?).await?;
+  R::from_response(response).await
+}
+
+/// The api url of a github enterprise server, from the url of the datasource instance.
+///
+/// Servers serve their api under `/api/v3`, which is added to urls without a path.
+fn api_url(url: &str) -> Option<String> {
+  let url = url.trim().trim_end_matches('/');
+  if url.is_empty() {
+    return None;
+  }
+
+  let host_only = match url.splitn(2, "://").nth(1) {
+    Some(rest) => !rest.contains('/'),
+    None => !url.contains('/'),
+  };
+  let api = url.contains("://api.") || url.starts_with("api.");
+
+  match host_only && !api {
+    true => Some(format!("{}/api/v3/", url)),
+    false => Some(format!("{}/", url)),
+  }
+}
+
+#[derive(Serialize)]
+struct AppClaims {
+  iat: i64,
+  exp: i64,
+  iss: String,
+}
+
+#[derive(Deserialize)]
+struct InstallationToken {
+  token: String,
+  expires_at: DateTime<Utc>,
+}
+
+/// The token a github app authenticates as itself with, to create installation tokens.
+fn app_jwt(app_id: u64, private_key: &str) -> Result<String> {
+  // backdated to allow for clock drift, as github recommends
+  let now = Utc::now();
+  let claims = AppClaims {
+    iat: (now - Duration::seconds(60)).timestamp(),
+    exp: (now + Duration::minutes(9)).timestamp(),
+    iss: app_id.to_string(),
+  };
+  let key = EncodingKey::from_rsa_pem(private_key.as_bytes())
+    .map_err(|e| anyhow!("invalid github app private key: {}", e))?;
+
+  Ok(jsonwebtoken::encode(
+    &Header::new(Algorithm::RS256),
+    &claims,
+    &key,
+  )?)
+}
+
+struct Client {
+  connection: Connection,
+  github: GitHub,
+  expires_at: Option<DateTime<Utc>>,
+}
+
+/// A github client and the limiter its calls go through.
+#[derive(Clone)]
+pub struct GitHub {
+  pub octocrab: Arc<Octocrab>,
+  /// Sends the requests of `octocrab` when the instance trusts a CA certificate of its own.
+  http: Option<reqwest::Client>,
+  pub limiter: RateLimiter,
+  pub api: Api,
+  /// Whether `LIKE` filters are searched for, which github matches on whole words only.
+  pub text_search: bool,
+  /// The rest api, e.g. `https://api.github.com/`.
+  pub rest_url: String,
+  pub graphql_url: String,
+  /// The rate limit cost of the GraphQL queries sent with the client.
+  pub cost: GraphQlCost,
+}
+
+impl GitHub {
+  /// Calls the github api through the limiter, which retries rate limits and connection
+  /// failures.
+  pub async fn call<T, F, Fut>(&self, mut f: F) -> Result<T>
+  where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = octocrab::Result<T>>,
+  {
+    self.limiter.run(|| call(self, f())).await
+  }
+
+  /// Gets a route of the rest api, like `Octocrab::get`.
+  pub async fn get<R, P>(&self, route: &str, parameters: Option<&P>) -> octocrab::Result<R>
+  where
+    R: FromResponse,
+    P: Serialize + ?Sized,
+  {
+    get(&self.octocrab, self.http.as_ref(), route, parameters).await
+  }
+
+  /// Posts to a route of the rest api, or the GraphQL endpoint, like `Octocrab::post`.
+  pub async fn post<R, B>(&self, route: &str, body: Option<&B>) -> octocrab::Result<R>
+  where
+    R: FromResponse,
+    B: Serialize + ?Sized,
+  {
+    post(&self.octocrab, self.http.as_ref(), route, body).await
+  }
+}
+
+/// Github clients authenticated with the credentials of each datasource instance.
+///
+/// Clients are rebuilt when the url or credentials of their instance change, and installation
+/// tokens are renewed before they expire. Github rate limits each token separately, so every
+/// instance has a rate limiter of its own.
+#[derive(Clone)]
+pub struct Clients {
+  policy: RetryPolicy,
+  clients: Arc<Mutex<HashMap<(i64, i64), Client>>>,
+}
+
+// credentials are kept out of logs
+impl fmt::Debug for Clients {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let instances = self.clients.lock().map(|c| c.len()).unwrap_or_default();
+    f.debug_struct("Clients")
+      .field("policy", &self.policy)
+      .field("instances", &instances)
+      .finish()
+  }
+}
+
+impl Clients {
+  pub fn new(policy: RetryPolicy) -> Self {
+    Self {
+      policy,
+      clients: Arc::new(Mutex::new(HashMap::new())),
+    }
+  }
+
+  /// The client of the datasource instance the current query was sent for.
+  pub async fn current(&self) -> Result<GitHub> {
+    let context = current_plugin_context();
+    let connection = Connection::from_context(context.as_ref())?;
+    self.get(context.as_ref(), connection).await
+  }
+
+  async fn get(&self, context: Option<&PluginContext>, connection: Connection) -> Result<GitHub> {
+    let instance = context
+      .map(|c| {
+        let id = c.data_source_instance_settings.as_ref().map_or(0, |s| s.id);
+        (c.org_id, id)
+      })
+      .unwrap_or_default();
+
+    // renewed tokens of the same connection share their rate limit
+    let (limiter, cost) = {
+      let clients = self
+        .clients
+        .lock()
+        .map_err(|_| anyhow!("github clients lock poisoned"))?;
+      let renew_at = Utc::now() + Duration::minutes(5);
+      match clients.get(&instance) {
+        Some(c) if c.connection == connection => match c.expires_at {
+          Some(t) if t <= renew_at => (c.github.limiter.clone(), c.github.cost.clone()),
+          _ => return Ok(c.github.clone()),
+        },
+        _ => (
+          RateLimiter::new(self.policy.clone()),
+          GraphQlCost::default(),
+        ),
+      }
+    };
+
+    let (octocrab, http, expires_at) = connection.connect().await?;
+    let github = GitHub {
+      octocrab,
+      http,
+      limiter,
+      api: connection.api,
+      text_search: connection.text_search,
+      rest_url: connection.rest_url(),
+      graphql_url: connection.graphql_url(),
+      cost,
+    };
+    self
+      .clients
+      .lock()
+      .map_err(|_| anyhow!("github clients lock poisoned"))?
+      .insert(
+        instance,
+        Client {
+          connection,
+          github: github.clone(),
+          expires_at,
+        },
+      );
+
+    Ok(github)
+  }
+}
+
+/// The rate limits of every instance, and the cost of their GraphQL queries, added up.
+impl Reporter for Clients {
+  fn kind(&self) -> &'static str {
+    "rate_limit"
+  }
+
+  fn report(&self) -> serde_json::Value {
+    let clients = match self.clients.lock() {
+      Ok(c) => c,
+      Err(_) => return serde_json::Value::Null,
+    };
+
+    let cost = clients
+      .values()
+      .map(|c| c.github.cost.stats())
+      .fold(CostStats::default(), CostStats::add);
+    let stats = clients.values().map(|c| c.github.limiter.stats()).fold(
+      RateLimitStats::default(),
+      |total, s| RateLimitStats {
+        attempts: total.attempts + s.attempts,
+        retries: total.retries + s.retries,
+        rate_limited: total.rate_limited + s.rate_limited,
+        failures: total.failures + s.failures,
+        in_flight: total.in_flight + s.in_flight,
+        throttled_ms: total.throttled_ms.max(s.throttled_ms),
+      },
+    );
+
+    let mut report = serde_json::to_value(stats).unwrap_or_default();
+    report["graphql"] = serde_json::to_value(cost).unwrap_or_default();
+    report
+  }
+}
+
+#[async_trait]
+impl HealthCheck for Clients {
+  async fn check(&self, context: Option<&PluginContext>) -> Result<String> {
+    let connection = Connection::from_context(context)?;
+    let github = self.get(context, connection.clone()).await?;
+
+    match connection.credentials {
+      Credentials::Anonymous => {
+        // the only anonymous check is that the api can be reached
+        let _: serde_json::Value = github
+          .get("/meta", None::<&()>)
+          .await
+          .map_err(|e| anyhow!("failed to reach the github api: {}", e))?;
+        Ok("No credentials are configured, so only public repositories can be queried".to_owned())
+      }
+      Credentials::Token(_) => {
+        let user: serde_json::Value = github
+          .get("/user", None::<&()>)
+          .await
+          .map_err(|e| anyhow!("invalid github access token: {}", e))?;
+        Ok(format!(
+          "Authenticated as {}",
+          user["login"].as_str().unwrap_or("unknown")
+        ))
+      }
+      Credentials::App {
+        installation_id, ..
+      } => {
+        let repos: serde_json::Value = github
+          .get("/installation/repositories", Some(&[("per_page", "1")]))
+          .await
+          .map_err(|e| anyhow!("invalid github app installation: {}", e))?;
+        Ok(format!(
+          "Authenticated as github app installation {}, with access to {} repositories",
+          installation_id, repos["total_count"]
+        ))
+      }
+    }
+  }
+}
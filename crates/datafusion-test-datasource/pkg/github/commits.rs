@@ -0,0 +1,228 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+use arrow::datatypes::SchemaRef;
+use async_trait::async_trait;
+use chrono::{DateTime, SecondsFormat, Utc};
+use futures::stream::{self, StreamExt, TryStreamExt};
+use grafana_plugin_sdk::{
+  column_requested, FilterOperator, GrafanaTable, Page, PageToken, PagedDataSource,
+  TableCapabilities, TableSchema, TimeRange,
+};
+use octocrab::Page as GitHubPage;
+use serde::{Deserialize, Serialize};
+
+use super::client::GitHub;
+use super::{next_page, page_number, parse_time, Account, Clients};
+
+// the most commits whose stats are fetched at once
+const STATS_CONCURRENCY: usize = 4;
+
+#[derive(Serialize, GrafanaTable, Debug, Clone)]
+pub struct Commit {
+  sha: String,
+  message: String,
+  author_name: String,
+  author_email: String,
+  // the github accounts of the author and committer, if their emails belong to one
+  author_login: Option<String>,
+  committer_name: String,
+  committer_email: String,
+  committer_login: Option<String>,
+  authored_at: DateTime<Utc>,
+  #[table(time, filter(gt_eq, lt_eq))]
+  committed_at: DateTime<Utc>,
+  parents: u32,
+  // these fields take a request for each commit, so they are only fetched when they are read
+  additions: Option<u64>,
+  deletions: Option<u64>,
+  // these fields are passed to the github api as filters
+  #[table(filter)]
+  branch: Option<String>,
+  #[table(filter)]
+  path: Option<String>,
+  #[table(filter)]
+  author: Option<String>,
+  #[table(required)]
+  owner: String,
+  #[table(required)]
+  repo: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct GitHubCommit {
+  sha: String,
+  commit: CommitDetails,
+  author: Option<Account>,
+  committer: Option<Account>,
+  parents: Vec<serde_json::Value>,
+}
+
+#[derive(Deserialize, Debug)]
+struct CommitDetails {
+  message: String,
+  author: Signature,
+  committer: Signature,
+}
+
+#[derive(Deserialize, Debug)]
+struct Signature {
+  name: String,
+  email: String,
+  date: DateTime<Utc>,
+}
+
+#[derive(Deserialize, Debug)]
+struct CommitStats {
+  stats: Stats,
+}
+
+#[derive(Deserialize, Debug)]
+struct Stats {
+  additions: u64,
+  deletions: u64,
+}
+
+#[derive(Serialize)]
+struct CommitParams<'a> {
+  #[serde(skip_serializing_if = "Option::is_none")]
+  sha: Option<&'a str>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  path: Option<&'a str>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  author: Option<&'a str>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  since: Option<String>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  until: Option<String>,
+  per_page: u8,
+  page: u32,
+}
+
+#[derive(Debug, Clone)]
+pub struct CommitTable {
+  clients: Clients,
+}
+
+impl CommitTable {
+  pub fn new(clients: Clients) -> Self {
+    Self { clients }
+  }
+}
+
+/// The additions and deletions of a commit.
+async fn fetch_stats(github: &GitHub, owner: &str, repo: &str, sha: &str) -> Result<Stats> {
+  let route = format!("/repos/{}/{}/commits/{}", owner, repo, sha);
+  let commit: CommitStats = github.call(|| github.get(&route, None::<&()>)).await?;
+  Ok(commit.stats)
+}
+
+#[async_trait]
+impl PagedDataSource for CommitTable {
+  type Row = Commit;
+
+  fn schema(&self) -> SchemaRef {
+    Commit::table_schema()
+  }
+
+  fn capabilities(&self) -> TableCapabilities {
+    TableCapabilities {
+      projection: true,
+      ..Commit::capabilities()
+    }
+  }
+
+  async fn fetch_page(
+    &self,
+    options: &HashMap<String, String>,
+    time_range: Option<TimeRange>,
+    page: PageToken,
+  ) -> Result<Page<Self::Row>> {
+    let owner = options.get("owner").unwrap_or(&"".to_owned()).clone();
+    let repo = options.get("repo").unwrap_or(&"".to_owned()).clone();
+    let branch = options.get("branch").cloned();
+    let path = options.get("path").cloned();
+    let author = options.get("author").cloned();
+
+    let filter = |op: FilterOperator| match options.get(&op.option_key("committed_at")) {
+      Some(value) => parse_time(value).map(Some),
+      None => Ok(None),
+    };
+    let (after, before) = (filter(FilterOperator::GtEq)?, filter(FilterOperator::LtEq)?);
+
+    // the dashboard time range is applied to the rows again, so it only has to narrow the
+    // request
+    let since = after.max(time_range.map(|r| r.from));
+    let until = match (before, time_range.map(|r| r.to)) {
+      (Some(before), Some(to)) => Some(before.min(to)),
+      (before, to) => before.or(to),
+    };
+
+    let params = CommitParams {
+      sha: branch.as_deref(),
+      path: path.as_deref(),
+      author: author.as_deref(),
+      since: since.map(|t| t.to_rfc3339_opts(SecondsFormat::Secs, true)),
+      until: until.map(|t| t.to_rfc3339_opts(SecondsFormat::Secs, true)),
+      per_page: 100,
+      page: page_number(&page)?,
+    };
+    let route = format!("/repos/{}/{}/commits", owner, repo);
+    let github = self.clients.current().await?;
+    let mut commits: GitHubPage<GitHubCommit> =
+      github.call(|| github.get(&route, Some(&params))).await?;
+    let (next, total_pages) = next_page(&commits);
+
+    // the api only filters by the second, the filters may be more precise
+    let commits: Vec<GitHubCommit> = commits
+      .take_items()
+      .into_iter()
+      .filter(|c| after.map_or(true, |after| c.commit.committer.date >= after))
+      .filter(|c| before.map_or(true, |before| c.commit.committer.date <= before))
+      .collect();
+
+    let stats: Vec<Option<Stats>> =
+      match column_requested(options, "additions") || column_requested(options, "deletions") {
+        true => {
+          stream::iter(commits.iter())
+            .map(|c| fetch_stats(&github, &owner, &repo, &c.sha))
+            .buffered(STATS_CONCURRENCY)
+            .map_ok(Some)
+            .try_collect()
+            .await?
+        }
+        false => commits.iter().map(|_| None).collect(),
+      };
+
+    let rows = commits
+      .into_iter()
+      .zip(stats)
+      .map(|(c, stats)| Commit {
+        sha: c.sha,
+        message: c.commit.message,
+        author_name: c.commit.author.name,
+        author_email: c.commit.author.email,
+        author_login: c.author.map(|a| a.login),
+        committer_name: c.commit.committer.name,
+        committer_email: c.commit.committer.email,
+        committer_login: c.committer.map(|a| a.login),
+        authored_at: c.commit.author.date,
+        committed_at: c.commit.committer.date,
+        parents: c.parents.len() as u32,
+        additions: stats.as_ref().map(|s| s.additions),
+        deletions: stats.as_ref().map(|s| s.deletions),
+        branch: branch.clone(),
+        path: path.clone(),
+        author: author.clone(),
+        owner: owner.clone(),
+        repo: repo.clone(),
+      })
+      .collect();
+
+    Ok(Page {
+      rows,
+      next,
+      total_pages,
+    })
+  }
+}
@@ -0,0 +1,290 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use grafana_plugin_sdk::{column_requested, PageToken, RetryableError};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use super::call;
+use super::client::GitHub;
+use super::search::check_total_count;
+
+const SEARCH_QUERY: &str = "query($query: String!, $after: String) {
+  rateLimit { cost remaining resetAt }
+  page: search(query: $query, type: ISSUE, first: 100, after: $after) {
+    issueCount
+    pageInfo { hasNextPage endCursor }
+    nodes { ... on {type} { {fields} } }
+  }
+}";
+
+impl GitHub {
+  /// Sends a GraphQL query through the limiter, and records its cost if it selects `rateLimit`.
+  pub(super) async fn graphql<T: DeserializeOwned>(
+    &self,
+    query: &str,
+    variables: serde_json::Value,
+  ) -> Result<T> {
+    let request = &json!({ "query": query, "variables": variables });
+    let response: Response<T> = self
+      .limiter
+      .run(|| async move {
+        let response: Response<T> = call(self, self.post(&self.graphql_url, Some(request))).await?;
+        response.check()
+      })
+      .await?;
+
+    let data = response
+      .data
+      .ok_or_else(|| anyhow!("github graphql query returned no data"))?;
+    if let Some(rate_limit) = &data.rate_limit {
+      self.cost.record(rate_limit);
+    }
+    Ok(data.data)
+  }
+}
+
+#[derive(Deserialize, Debug)]
+struct Response<T> {
+  data: Option<Data<T>>,
+  #[serde(default)]
+  errors: Vec<QueryError>,
+}
+
+impl<T> Response<T> {
+  /// Fails on errors, which github answers with a 200, marking rate limits as retryable.
+  fn check(self) -> Result<Self> {
+    if self.errors.is_empty() {
+      return Ok(self);
+    }
+
+    let message = self
+      .errors
+      .iter()
+      .map(|e| e.message.as_str())
+      .collect::<Vec<&str>>()
+      .join("; ");
+    match self
+      .errors
+      .iter()
+      .any(|e| e.kind.as_deref() == Some("RATE_LIMITED"))
+    {
+      true => Err(RetryableError::rate_limited(message, None).into()),
+      false => Err(anyhow!("github graphql query failed: {}", message)),
+    }
+  }
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct Data<T> {
+  rate_limit: Option<RateLimit>,
+  #[serde(flatten)]
+  data: T,
+}
+
+#[derive(Deserialize, Debug)]
+struct QueryError {
+  #[serde(rename = "type")]
+  kind: Option<String>,
+  message: String,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct RateLimit {
+  cost: u64,
+  remaining: u64,
+  reset_at: DateTime<Utc>,
+}
+
+/// The GraphQL rate limit points spent by a client.
+///
+/// Github limits each token to a number of points an hour, and a query costs more points the
+/// more nodes it could return.
+#[derive(Debug, Clone, Default)]
+pub struct GraphQlCost(Arc<Mutex<CostStats>>);
+
+impl GraphQlCost {
+  fn record(&self, rate_limit: &RateLimit) {
+    if let Ok(mut stats) = self.0.lock() {
+      stats.queries += 1;
+      stats.cost += rate_limit.cost;
+      stats.remaining = Some(rate_limit.remaining);
+      stats.reset_at = Some(rate_limit.reset_at);
+    }
+  }
+
+  pub fn stats(&self) -> CostStats {
+    self.0.lock().map(|s| s.clone()).unwrap_or_default()
+  }
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CostStats {
+  pub queries: u64,
+  pub cost: u64,
+  /// The points left when the last query was sent, until `reset_at`.
+  pub remaining: Option<u64>,
+  pub reset_at: Option<DateTime<Utc>>,
+}
+
+impl CostStats {
+  /// The stats of two clients added up, with the points left of the one closest to its limit.
+  pub fn add(self, other: Self) -> Self {
+    let (remaining, reset_at) = match (self.remaining, other.remaining) {
+      (Some(a), Some(b)) if b < a => (other.remaining, other.reset_at),
+      (None, _) => (other.remaining, other.reset_at),
+      _ => (self.remaining, self.reset_at),
+    };
+
+    CostStats {
+      queries: self.queries + other.queries,
+      cost: self.cost + other.cost,
+      remaining,
+      reset_at,
+    }
+  }
+}
+
+/// The cursor of a page of a GraphQL connection, which is absent for the first one.
+pub(super) fn cursor(page: &PageToken) -> Result<Option<String>> {
+  match page {
+    PageToken::First => Ok(None),
+    PageToken::Cursor(cursor) => Ok(Some(cursor.clone())),
+    page => Err(anyhow!("github graphql pages are cursors, not {:?}", page)),
+  }
+}
+
+/// A page of the nodes of a GraphQL connection.
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct Nodes<T> {
+  pub(super) nodes: Vec<T>,
+  page_info: PageInfo,
+}
+
+impl<T> Nodes<T> {
+  pub(super) fn next(&self) -> Option<PageToken> {
+    match (self.page_info.has_next_page, &self.page_info.end_cursor) {
+      (true, Some(cursor)) => Some(PageToken::Cursor(cursor.clone())),
+      _ => None,
+    }
+  }
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct PageInfo {
+  has_next_page: bool,
+  end_cursor: Option<String>,
+}
+
+/// The nodes of a connection that is read without paging, e.g. the labels of an issue.
+#[derive(Deserialize, Debug)]
+pub(super) struct List<T> {
+  pub(super) nodes: Vec<T>,
+}
+
+/// A connection that only the size of is read.
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct Count {
+  pub(super) total_count: u64,
+}
+
+/// A connection aliased as `page` in a query.
+#[derive(Deserialize, Debug)]
+struct Paged<T> {
+  page: Nodes<T>,
+}
+
+/// The results of a search aliased as `page`, and how many there are in all.
+#[derive(Deserialize, Debug)]
+struct Found<T> {
+  page: FoundNodes<T>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct FoundNodes<T> {
+  issue_count: u32,
+  #[serde(flatten)]
+  nodes: Nodes<T>,
+}
+
+/// The repo a connection of is aliased as `page`, which is null if there is no such repo.
+#[derive(Deserialize, Debug)]
+struct RepositoryPage<T> {
+  repository: Option<Paged<T>>,
+}
+
+/// A page of the connection of a repo that is aliased as `page` in `query`.
+pub(super) async fn repository_page<T: DeserializeOwned>(
+  github: &GitHub,
+  query: &str,
+  variables: serde_json::Value,
+) -> Result<Nodes<T>> {
+  let data: RepositoryPage<T> = github.graphql(query, variables).await?;
+  data
+    .repository
+    .map(|r| r.page)
+    .ok_or_else(|| anyhow!("github repository not found"))
+}
+
+/// A page of the issues or pull requests, as `kind` says, that a search finds, with the
+/// selected fields of each.
+pub(super) async fn search_page<T: DeserializeOwned>(
+  github: &GitHub,
+  search: String,
+  kind: &str,
+  selection: &Selection,
+  after: Option<String>,
+) -> Result<Nodes<T>> {
+  let query = SEARCH_QUERY
+    .replace("{type}", kind)
+    .replace("{fields}", &selection.to_string());
+  let variables = json!({ "query": search, "after": after });
+  let data: Found<T> = github.graphql(&query, variables).await?;
+  check_total_count(&search, data.page.issue_count)?;
+  Ok(data.page.nodes)
+}
+
+/// The fields of a GraphQL node that a query reads, out of the ones each column is read from.
+#[derive(Debug, Default)]
+pub(super) struct Selection {
+  fields: Vec<&'static str>,
+}
+
+impl Selection {
+  /// Reads `fields` whichever columns are read, e.g. the ones rows are filtered on.
+  pub(super) fn field(&mut self, fields: &'static str) {
+    if !self.fields.contains(&fields) {
+      self.fields.push(fields);
+    }
+  }
+
+  /// Reads the fields of each column the query reads, which can be shared by several columns.
+  pub(super) fn columns(
+    &mut self,
+    options: &HashMap<String, String>,
+    columns: &[(&'static str, &'static str)],
+  ) {
+    for &(column, fields) in columns {
+      if column_requested(options, column) {
+        self.field(fields);
+      }
+    }
+  }
+}
+
+impl fmt::Display for Selection {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str(&self.fields.join(" "))
+  }
+}
@@ -0,0 +1,346 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+use arrow::datatypes::SchemaRef;
+use async_trait::async_trait;
+use chrono::{DateTime, SecondsFormat, Utc};
+use grafana_plugin_sdk::{
+  FilterOperator, GrafanaTable, Page, PageToken, PagedDataSource, TableCapabilities, TableSchema,
+  TimeRange,
+};
+use octocrab::Page as GitHubPage;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use super::client::Api;
+use super::graphql::{cursor, repository_page, search_page, Count, List, Nodes, Selection};
+use super::search::Search;
+use super::{next_page, page_number, parse_time, Account, Clients, Label};
+
+#[derive(Serialize, GrafanaTable, Debug, Clone)]
+pub struct Issue {
+  number: i64,
+  #[table(filter(like))]
+  title: String,
+  #[table(filter(like))]
+  body: Option<String>,
+  #[table(filter)]
+  state: String,
+  labels: Vec<String>,
+  assignees: Vec<String>,
+  #[table(filter)]
+  author: String,
+  comments: u32,
+  #[table(time, filter(gt_eq, lt_eq))]
+  created_at: DateTime<Utc>,
+  #[table(filter(gt_eq))]
+  updated_at: DateTime<Utc>,
+  closed_at: Option<DateTime<Utc>>,
+  // the rest api also lists pull requests as issues, the GraphQL api doesn't
+  is_pull_request: bool,
+  // these fields are passed to the github api as filters
+  #[table(filter)]
+  label: Option<String>,
+  #[table(required)]
+  owner: String,
+  // queries without a repo filter read every repo of the owner
+  #[table(filter)]
+  repo: String,
+  #[table(filter)]
+  archived: bool,
+}
+
+#[derive(Deserialize, Debug)]
+struct GitHubIssue {
+  number: i64,
+  title: String,
+  body: Option<String>,
+  state: String,
+  labels: Vec<Label>,
+  assignees: Vec<Account>,
+  user: Account,
+  comments: u32,
+  created_at: DateTime<Utc>,
+  updated_at: DateTime<Utc>,
+  closed_at: Option<DateTime<Utc>>,
+  pull_request: Option<serde_json::Value>,
+}
+
+/// An issue from the GraphQL api, with only the fields of the columns a query reads.
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct IssueNode {
+  number: i64,
+  created_at: DateTime<Utc>,
+  updated_at: DateTime<Utc>,
+  // null for deleted accounts
+  author: Option<Account>,
+  #[serde(default)]
+  title: String,
+  body: Option<String>,
+  #[serde(default)]
+  state: String,
+  labels: Option<List<Label>>,
+  assignees: Option<List<Account>>,
+  comments: Option<Count>,
+  closed_at: Option<DateTime<Utc>>,
+}
+
+impl From<IssueNode> for GitHubIssue {
+  fn from(issue: IssueNode) -> Self {
+    GitHubIssue {
+      number: issue.number,
+      title: issue.title,
+      body: issue.body,
+      state: issue.state.to_lowercase(),
+      labels: issue.labels.map_or_else(Vec::new, |l| l.nodes),
+      assignees: issue.assignees.map_or_else(Vec::new, |a| a.nodes),
+      // github shows deleted accounts as ghost
+      user: issue.author.unwrap_or_else(|| Account {
+        login: "ghost".to_owned(),
+      }),
+      comments: issue.comments.map_or(0, |c| c.total_count as u32),
+      created_at: issue.created_at,
+      updated_at: issue.updated_at,
+      closed_at: issue.closed_at,
+      // the GraphQL api lists pull requests apart from issues
+      pull_request: None,
+    }
+  }
+}
+
+const ISSUES_QUERY: &str = "query($owner: String!, $repo: String!, $states: [IssueState!], $filters: IssueFilters, $after: String) {
+  rateLimit { cost remaining resetAt }
+  repository(owner: $owner, name: $repo) {
+    page: issues(first: 100, after: $after, states: $states, filterBy: $filters, orderBy: {field: UPDATED_AT, direction: DESC}) {
+      pageInfo { hasNextPage endCursor }
+      nodes { {fields} }
+    }
+  }
+}";
+
+const LABELS_FIELD: &str = "labels(first: 100) { nodes { name } }";
+
+// the fields of an issue that each column is read from
+const ISSUE_FIELDS: &[(&str, &str)] = &[
+  ("title", "title"),
+  ("body", "body"),
+  ("state", "state"),
+  ("labels", LABELS_FIELD),
+  ("assignees", "assignees(first: 100) { nodes { login } }"),
+  ("comments", "comments { totalCount }"),
+  ("closed_at", "closedAt"),
+];
+
+#[derive(Serialize)]
+struct IssueParams<'a> {
+  state: &'a str,
+  sort: &'a str,
+  direction: &'a str,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  labels: Option<&'a str>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  creator: Option<&'a str>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  since: Option<String>,
+  per_page: u8,
+  page: u32,
+}
+
+#[derive(Debug, Clone)]
+pub struct IssueTable {
+  clients: Clients,
+}
+
+impl IssueTable {
+  pub fn new(clients: Clients) -> Self {
+    Self { clients }
+  }
+}
+
+#[async_trait]
+impl PagedDataSource for IssueTable {
+  type Row = Issue;
+
+  fn schema(&self) -> SchemaRef {
+    Issue::table_schema()
+  }
+
+  fn capabilities(&self) -> TableCapabilities {
+    TableCapabilities {
+      projection: true,
+      ..Issue::capabilities()
+    }
+  }
+
+  async fn fetch_page(
+    &self,
+    options: &HashMap<String, String>,
+    _time_range: Option<TimeRange>,
+    page: PageToken,
+  ) -> Result<Page<Self::Row>> {
+    let owner = options.get("owner").unwrap_or(&"".to_owned()).clone();
+    let repo = options.get("repo").unwrap_or(&"".to_owned()).clone();
+    // set by `Repos` for every repo
+    let archived = options.get("archived").map_or(Ok(false), |a| a.parse())?;
+    let author = options.get("author");
+    let label = options.get("label").cloned();
+    let filter = |op: FilterOperator| match options.get(&op.option_key("created_at")) {
+      Some(value) => parse_time(value).map(Some),
+      None => Ok(None),
+    };
+    let (after, before) = (filter(FilterOperator::GtEq)?, filter(FilterOperator::LtEq)?);
+
+    // issues are open or closed, the api lists both for "all"
+    let state = match options.get("state").map(String::as_str) {
+      None => "all",
+      Some(state @ "open") | Some(state @ "closed") => state,
+      Some(_) => {
+        return Ok(Page {
+          rows: vec![],
+          next: None,
+          total_pages: None,
+        })
+      }
+    };
+    let since = match options.get(&FilterOperator::GtEq.option_key("updated_at")) {
+      Some(since) => Some(parse_time(since)?),
+      None => None,
+    };
+
+    let github = self.clients.current().await?;
+
+    // listing issues filters by label, author and update, but only search matches text
+    let mut search = Search::default();
+    if github.text_search {
+      search.like(options, "title");
+      search.like(options, "body");
+    }
+    let searching = !search.is_empty();
+    if searching {
+      if let Some(author) = author {
+        search.qualifier("author", author);
+      }
+      if let Some(label) = &label {
+        search.qualifier("label", label);
+      }
+      search.time("created", after, before);
+      search.time("updated", since, None);
+    }
+
+    let (issues, next, total_pages) = match (github.api, searching) {
+      (Api::Rest, false) => {
+        let params = IssueParams {
+          state,
+          sort: "updated",
+          direction: "desc",
+          labels: label.as_deref(),
+          creator: author.map(String::as_str),
+          since: since.map(|t| t.to_rfc3339_opts(SecondsFormat::Secs, true)),
+          per_page: 100,
+          page: page_number(&page)?,
+        };
+        let route = format!("/repos/{}/{}/issues", owner, repo);
+        let mut issues: GitHubPage<GitHubIssue> =
+          github.call(|| github.get(&route, Some(&params))).await?;
+        let (next, total_pages) = next_page(&issues);
+        (issues.take_items(), next, total_pages)
+      }
+      (Api::Rest, true) => {
+        let state = format!("is:{}", state);
+        let qualifiers: Vec<&str> = match state.as_str() {
+          "is:all" => vec![],
+          state => vec![state],
+        };
+        search
+          .fetch(&github, &owner, &repo, &qualifiers, page_number(&page)?)
+          .await?
+      }
+      (Api::GraphQl, _) => {
+        let mut selection = Selection::default();
+        selection.field("number createdAt updatedAt author { login }");
+        if label.is_some() {
+          selection.field(LABELS_FIELD);
+        }
+        selection.columns(options, ISSUE_FIELDS);
+
+        let after = cursor(&page)?;
+        let issues: Nodes<IssueNode> = match searching {
+          false => {
+            let states = match state {
+              "open" => json!(["OPEN"]),
+              "closed" => json!(["CLOSED"]),
+              _ => serde_json::Value::Null,
+            };
+            let filters = json!({
+              "labels": label.as_ref().map(|l| vec![l]),
+              "createdBy": author,
+              "since": since.map(|t| t.to_rfc3339_opts(SecondsFormat::Secs, true)),
+            });
+            let graphql = ISSUES_QUERY.replace("{fields}", &selection.to_string());
+            let variables = json!({
+              "owner": owner,
+              "repo": repo,
+              "states": states,
+              "filters": filters,
+              "after": after,
+            });
+            repository_page(&github, &graphql, variables).await?
+          }
+          true => {
+            let state = format!("is:{}", state);
+            let mut qualifiers = vec!["is:issue", "sort:updated-desc"];
+            if state != "is:all" {
+              qualifiers.push(state.as_str());
+            }
+            let search = search.query(&owner, &repo, &qualifiers);
+            search_page(&github, search, "Issue", &selection, after).await?
+          }
+        };
+
+        let next = issues.next();
+        let issues = issues.nodes.into_iter().map(GitHubIssue::from).collect();
+        (issues, next, None)
+      }
+    };
+
+    // the api only filters by the second and search matches logins and labels regardless of
+    // case, the filters may be more precise
+    let rows = issues
+      .into_iter()
+      .filter(|i| since.map_or(true, |since| i.updated_at >= since))
+      .filter(|i| author.map_or(true, |a| *a == i.user.login))
+      .filter(|i| {
+        label
+          .as_ref()
+          .map_or(true, |l| i.labels.iter().any(|il| il.name == *l))
+      })
+      .filter(|i| after.map_or(true, |after| i.created_at >= after))
+      .filter(|i| before.map_or(true, |before| i.created_at <= before))
+      .map(|i| Issue {
+        number: i.number,
+        title: i.title,
+        body: i.body,
+        state: i.state,
+        labels: i.labels.into_iter().map(|l| l.name).collect(),
+        assignees: i.assignees.into_iter().map(|a| a.login).collect(),
+        author: i.user.login,
+        comments: i.comments,
+        created_at: i.created_at,
+        updated_at: i.updated_at,
+        closed_at: i.closed_at,
+        is_pull_request: i.pull_request.is_some(),
+        label: label.clone(),
+        owner: owner.clone(),
+        repo: repo.clone(),
+        archived,
+      })
+      .collect();
+
+    Ok(Page {
+      rows,
+      next,
+      total_pages,
+    })
+  }
+}
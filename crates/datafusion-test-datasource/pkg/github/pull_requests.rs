@@ -1,152 +1,682 @@
-use std::collections::{BTreeMap, HashMap};
-use std::io::Cursor;
-use std::sync::Arc;
+use std::collections::HashMap;
 
 use anyhow::Result;
-use arrow::datatypes::{DataType, Field, Schema, SchemaRef, TimeUnit};
+use arrow::datatypes::SchemaRef;
 use async_trait::async_trait;
-use cached::proc_macro::cached;
-use grafana_plugin_sdk::DataSource;
-use octocrab::models::pulls::PullRequest as GitHubPull;
-use octocrab::models::IssueState;
-use octocrab::params::State;
-use serde::Serialize;
-
-#[derive(Serialize, Debug, Clone)]
+use chrono::{DateTime, Utc};
+use futures::stream::{self, StreamExt, TryStreamExt};
+use grafana_plugin_sdk::{
+  column_requested, FilterOperator, GrafanaTable, Page, PageToken, PagedDataSource,
+  TableCapabilities, TableSchema, TimeRange, SINCE_OPTION,
+};
+use octocrab::Page as GitHubPage;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use super::client::{Api, GitHub};
+use super::graphql::{cursor, repository_page, search_page, Count, List, Nodes, Selection};
+use super::reviews::{fetch_reviews, GitHubReview};
+use super::search::Search;
+use super::{next_page, page_number, parse_time, Account, Clients, Label};
+
+// the most pull requests whose details or reviews are fetched at once
+const DETAILS_CONCURRENCY: usize = 4;
+
+// the columns read from each pull request, and from its reviews
+const DETAIL_COLUMNS: &[&str] = &["additions", "deletions", "changed_files", "commits"];
+const REVIEW_COLUMNS: &[&str] = &["reviewers", "reviews", "first_review_at"];
+
+#[derive(Serialize, GrafanaTable, Debug, Clone)]
 pub struct PullRequest {
+  #[table(key)]
   id: u64,
-  created_at: i64,
-  updated_at: Option<i64>,
-  closed_at: Option<i64>,
-  merged_at: Option<i64>,
-  state: String,
+  number: u64,
+  #[table(time, filter(gt_eq, lt_eq))]
+  created_at: DateTime<Utc>,
+  #[table(watermark)]
+  updated_at: Option<DateTime<Utc>>,
+  merged_at: Option<DateTime<Utc>>,
+  closed_at: Option<DateTime<Utc>>,
+  #[table(filter(like))]
   title: String,
+  #[table(filter(like))]
   body: Option<String>,
   url: String,
+  #[table(filter)]
+  state: String,
+  #[table(filter)]
   author: String,
+  draft: bool,
+  labels: Vec<String>,
+  requested_reviewers: Vec<String>,
+  base_branch: String,
+  head_branch: String,
+  merge_commit_sha: Option<String>,
+  // these fields take a request for each pull request, so they are only fetched when they are
+  // read
+  additions: Option<u64>,
+  deletions: Option<u64>,
+  changed_files: Option<u64>,
+  commits: Option<u64>,
+  reviewers: Option<Vec<String>>,
+  reviews: Option<u32>,
+  first_review_at: Option<DateTime<Utc>>,
+  // these fields are passed to the github api as filters
+  #[table(filter)]
+  label: Option<String>,
+  #[table(required)]
   owner: String,
+  // queries without a repo filter read every repo of the owner
+  #[table(filter)]
   repo: String,
+  #[table(filter)]
+  archived: bool,
 }
 
-// TODO: this should probably implement From/Into
-fn to_ts(d: chrono::DateTime<chrono::Utc>) -> i64 {
-  d.timestamp() * 1000 * 1000 * 1000
+#[derive(Deserialize, Debug)]
+pub(super) struct GitHubPull {
+  id: u64,
+  pub(super) number: u64,
+  pub(super) created_at: DateTime<Utc>,
+  pub(super) updated_at: Option<DateTime<Utc>>,
+  merged_at: Option<DateTime<Utc>>,
+  closed_at: Option<DateTime<Utc>>,
+  title: String,
+  body: Option<String>,
+  url: String,
+  state: String,
+  pub(super) user: Account,
+  #[serde(default)]
+  draft: bool,
+  labels: Vec<Label>,
+  #[serde(default)]
+  requested_reviewers: Vec<Account>,
+  base: Branch,
+  head: Branch,
+  merge_commit_sha: Option<String>,
 }
 
-// TODO: this should probably implement From/Into
-fn to_optional_ts(o: Option<chrono::DateTime<chrono::Utc>>) -> Option<i64> {
-  match o {
-    Some(d) => Some(to_ts(d)),
-    None => None,
-  }
+#[derive(Deserialize, Debug)]
+struct Branch {
+  #[serde(rename = "ref")]
+  name: String,
+}
+
+/// The fields only a single pull request has.
+#[derive(Deserialize, Debug)]
+struct PullDetails {
+  additions: u64,
+  deletions: u64,
+  changed_files: u64,
+  commits: u64,
+}
+
+/// A pull request found by a search.
+#[derive(Deserialize, Debug)]
+struct SearchedPull {
+  number: u64,
+}
+
+#[derive(Serialize)]
+struct PullParams<'a> {
+  state: &'a str,
+  sort: &'a str,
+  direction: &'a str,
+  per_page: u8,
+  page: u32,
+}
+
+/// A page of the pull requests of a repo in `state`, most recently updated first.
+pub(super) async fn fetch_pulls(
+  github: &GitHub,
+  owner: &str,
+  repo: &str,
+  state: &str,
+  page: u32,
+) -> Result<GitHubPage<GitHubPull>> {
+  let route = format!("/repos/{}/{}/pulls", owner, repo);
+  let params = PullParams {
+    state,
+    sort: "updated",
+    direction: "desc",
+    per_page: 100,
+    page,
+  };
+  github.call(|| github.get(&route, Some(&params))).await
+}
+
+/// A single pull request, which also has the fields of `PullDetails`.
+pub(super) async fn fetch_pull<T: DeserializeOwned>(
+  github: &GitHub,
+  owner: &str,
+  repo: &str,
+  number: u64,
+) -> Result<T> {
+  let route = format!("/repos/{}/{}/pulls/{}", owner, repo, number);
+  github.call(|| github.get(&route, None::<&()>)).await
 }
 
-#[cached(time = 600, result = true)]
-async fn cached_fetch(owner: String, repo: String) -> Result<Vec<GitHubPull>> {
-  let octocrab = octocrab::instance();
+/// A pull request from the GraphQL api, with only the fields of the columns a query reads.
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct PullNode {
+  database_id: u64,
+  number: u64,
+  created_at: DateTime<Utc>,
+  updated_at: Option<DateTime<Utc>>,
+  // null for deleted accounts
+  author: Option<Account>,
+  merged_at: Option<DateTime<Utc>>,
+  closed_at: Option<DateTime<Utc>>,
+  #[serde(default)]
+  title: String,
+  body: Option<String>,
+  #[serde(default)]
+  state: String,
+  #[serde(default)]
+  is_draft: bool,
+  labels: Option<List<Label>>,
+  review_requests: Option<List<ReviewRequest>>,
+  #[serde(default)]
+  base_ref_name: String,
+  #[serde(default)]
+  head_ref_name: String,
+  merge_commit: Option<MergeCommit>,
+  additions: Option<u64>,
+  deletions: Option<u64>,
+  changed_files: Option<u64>,
+  commits: Option<Count>,
+  reviews: Option<List<ReviewNode>>,
+}
 
-  let mut current_page = octocrab
-    .pulls(owner, repo)
-    .list()
-    .state(State::All)
-    .per_page(100)
-    .send()
-    .await?;
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct ReviewRequest {
+  // teams have no login
+  requested_reviewer: Option<Reviewer>,
+}
 
-  let mut pulls = current_page.take_items();
+#[derive(Deserialize, Debug)]
+struct Reviewer {
+  login: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+struct MergeCommit {
+  oid: String,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct ReviewNode {
+  author: Option<Account>,
+  submitted_at: Option<DateTime<Utc>>,
+}
 
-  while let Some(mut page) = octocrab.get_page::<GitHubPull>(&current_page.next).await? {
-    pulls.extend(page.take_items());
-    current_page = page;
+const PULLS_QUERY: &str = "query($owner: String!, $repo: String!, $states: [PullRequestState!], $after: String) {
+  rateLimit { cost remaining resetAt }
+  repository(owner: $owner, name: $repo) {
+    page: pullRequests(first: 100, after: $after, states: $states, orderBy: {field: UPDATED_AT, direction: DESC}) {
+      pageInfo { hasNextPage endCursor }
+      nodes { {fields} }
+    }
   }
+}";
 
-  Ok(pulls)
+// the fields rows are filtered on
+const FILTER_FIELDS: &str = "databaseId number createdAt updatedAt author { login }";
+const LABELS_FIELD: &str = "labels(first: 100) { nodes { name } }";
+// pending reviews are left out, and reviews after the first 100 of a pull request
+const REVIEWS_FIELD: &str = "reviews(first: 100, states: [APPROVED, CHANGES_REQUESTED, COMMENTED, DISMISSED]) { nodes { author { login } submittedAt } }";
+
+// the fields of a pull request that each column is read from
+const PULL_FIELDS: &[(&str, &str)] = &[
+  ("merged_at", "mergedAt"),
+  ("closed_at", "closedAt"),
+  ("title", "title"),
+  ("body", "body"),
+  ("state", "state"),
+  ("draft", "isDraft"),
+  ("labels", LABELS_FIELD),
+  (
+    "requested_reviewers",
+    "reviewRequests(first: 100) { nodes { requestedReviewer { ... on User { login } } } }",
+  ),
+  ("base_branch", "baseRefName"),
+  ("head_branch", "headRefName"),
+  ("merge_commit_sha", "mergeCommit { oid }"),
+  ("additions", "additions"),
+  ("deletions", "deletions"),
+  ("changed_files", "changedFiles"),
+  ("commits", "commits { totalCount }"),
+  ("reviewers", REVIEWS_FIELD),
+  ("reviews", REVIEWS_FIELD),
+  ("first_review_at", REVIEWS_FIELD),
+];
+
+/// The filters of a query of the pull requests of a repo.
+struct PullQuery {
+  owner: String,
+  repo: String,
+  // set by `Repos` for every repo
+  archived: bool,
+  // open, closed or all
+  state: &'static str,
+  author: Option<String>,
+  label: Option<String>,
+  since: Option<DateTime<Utc>>,
+  after: Option<DateTime<Utc>>,
+  before: Option<DateTime<Utc>>,
 }
 
-#[derive(Debug, Clone)]
-pub struct PullRequestTable;
+impl PullQuery {
+  /// The filters of a query, or none if no pull request matches them.
+  fn new(options: &HashMap<String, String>) -> Result<Option<Self>> {
+    // pull requests are open or closed, the api lists both for "all"
+    let state = match options.get("state").map(String::as_str) {
+      None => "all",
+      Some("open") => "open",
+      Some("closed") => "closed",
+      Some(_) => return Ok(None),
+    };
+    let since = match options.get(SINCE_OPTION) {
+      Some(since) => Some(DateTime::parse_from_rfc3339(since)?.with_timezone(&Utc)),
+      None => None,
+    };
+    let filter = |op: FilterOperator| match options.get(&op.option_key("created_at")) {
+      Some(value) => parse_time(value).map(Some),
+      None => Ok(None),
+    };
 
-#[async_trait]
-impl DataSource for PullRequestTable {
-  type Data = Cursor<Vec<u8>>;
+    Ok(Some(PullQuery {
+      owner: options.get("owner").unwrap_or(&"".to_owned()).clone(),
+      repo: options.get("repo").unwrap_or(&"".to_owned()).clone(),
+      archived: options.get("archived").map_or(Ok(false), |a| a.parse())?,
+      state,
+      author: options.get("author").cloned(),
+      label: options.get("label").cloned(),
+      since,
+      after: filter(FilterOperator::GtEq)?,
+      before: filter(FilterOperator::LtEq)?,
+    }))
+  }
 
-  fn schema(&self) -> SchemaRef {
-    let mut metadata = BTreeMap::new();
-    metadata.insert("filter".to_owned(), "true".to_owned());
-
-    let id = Field::new("id", DataType::UInt64, false);
-    let created_at = Field::new(
-      "created_at",
-      DataType::Timestamp(TimeUnit::Nanosecond, None),
-      false,
-    );
-    let updated_at = Field::new(
-      "updated_at",
-      DataType::Timestamp(TimeUnit::Nanosecond, None),
-      true,
-    );
-    let closed_at = Field::new(
-      "closed_at",
-      DataType::Timestamp(TimeUnit::Nanosecond, None),
-      true,
-    );
-    let merged_at = Field::new(
-      "merged_at",
-      DataType::Timestamp(TimeUnit::Nanosecond, None),
-      true,
-    );
-    let title = Field::new("title", DataType::Utf8, false);
-    let body = Field::new("body", DataType::Utf8, true);
-    let url = Field::new("url", DataType::Utf8, false);
-    let author = Field::new("author", DataType::Utf8, false);
-    let state = Field::new("state", DataType::Utf8, false);
-
-    // these fields are passed to the github api as filters
-    let mut owner = Field::new("owner", DataType::Utf8, false);
-    owner.set_metadata(Some(metadata.clone()));
-    let mut repo = Field::new("repo", DataType::Utf8, false);
-    repo.set_metadata(Some(metadata.clone()));
-
-    let schema = Schema::new(vec![
-      id, created_at, updated_at, merged_at, closed_at, title, body, url, state, author, owner,
-      repo,
-    ]);
-
-    Arc::new(schema)
+  /// The search for the `LIKE` filters that listing pull requests can't apply, with the other
+  /// filters as qualifiers. It is empty if there are none or `text_search` isn't set, and pull
+  /// requests are listed and filtered as they are fetched instead.
+  fn search(&self, options: &HashMap<String, String>, text_search: bool) -> Search {
+    let mut search = Search::default();
+    if text_search {
+      search.like(options, "title");
+      search.like(options, "body");
+    }
+    if search.is_empty() {
+      return search;
+    }
+
+    if let Some(author) = &self.author {
+      search.qualifier("author", author);
+    }
+    if let Some(label) = &self.label {
+      search.qualifier("label", label);
+    }
+    search.time("created", self.after, self.before);
+    search
   }
 
-  async fn fetch_results(&self, options: HashMap<String, String>) -> Result<Self::Data> {
-    let owner = options.get("owner").unwrap_or(&"".to_owned()).clone();
-    let repo = options.get("repo").unwrap_or(&"".to_owned()).clone();
-    let pulls = cached_fetch(owner, repo).await?;
-
-    let pulls: Vec<u8> = pulls
-      .into_iter()
-      .map(|r| PullRequest {
-        id: *r.id.as_ref(),
-        created_at: to_ts(r.created_at),
-        updated_at: to_optional_ts(r.updated_at),
-        closed_at: to_optional_ts(r.closed_at),
-        merged_at: to_optional_ts(r.merged_at),
+  /// The qualifiers that search for pull requests in the state.
+  fn qualifiers(&self) -> Vec<&'static str> {
+    match self.state {
+      "open" => vec!["is:pr", "is:open"],
+      "closed" => vec!["is:pr", "is:closed"],
+      _ => vec!["is:pr"],
+    }
+  }
+
+  fn changed(&self, updated_at: Option<DateTime<Utc>>) -> bool {
+    match (self.since, updated_at) {
+      (Some(since), Some(updated_at)) => updated_at >= since,
+      _ => true,
+    }
+  }
+
+  /// Whether a pull request matches the filters, as search matches logins and labels
+  /// regardless of case and times by the second.
+  fn matches<'a>(
+    &self,
+    author: &str,
+    mut labels: impl Iterator<Item = &'a str>,
+    created_at: DateTime<Utc>,
+    updated_at: Option<DateTime<Utc>>,
+  ) -> bool {
+    self.changed(updated_at)
+      && self.author.as_ref().map_or(true, |a| a == author)
+      && self
+        .label
+        .as_ref()
+        .map_or(true, |l| labels.any(|name| name == l.as_str()))
+      && self.after.map_or(true, |after| created_at >= after)
+      && self.before.map_or(true, |before| created_at <= before)
+  }
+}
+
+/// The reviewers, number of reviews and first review of a pull request by `author`, from the
+/// reviewer and submission time of each review.
+///
+/// Replies of the author and pending reviews are left out.
+fn summarize_reviews(
+  author: &str,
+  reviews: impl Iterator<Item = (Option<String>, Option<DateTime<Utc>>)>,
+) -> (Vec<String>, u32, Option<DateTime<Utc>>) {
+  let reviews: Vec<(Option<String>, DateTime<Utc>)> = reviews
+    .filter_map(|(reviewer, submitted_at)| submitted_at.map(|t| (reviewer, t)))
+    .filter(|(reviewer, _)| reviewer.as_deref() != Some(author))
+    .collect();
+
+  let mut reviewers: Vec<String> = reviews.iter().filter_map(|(r, _)| r.clone()).collect();
+  reviewers.sort();
+  reviewers.dedup();
+  let first_review_at = reviews.iter().map(|(_, t)| *t).min();
+
+  (reviewers, reviews.len() as u32, first_review_at)
+}
+
+/// A page of pull requests from the rest api, which takes a request for the details and for
+/// the reviews of each pull request.
+async fn fetch_rest_page(
+  github: &GitHub,
+  options: &HashMap<String, String>,
+  query: &PullQuery,
+  number: u32,
+) -> Result<Page<PullRequest>> {
+  let (owner, repo) = (&query.owner, &query.repo);
+  let mut search = query.search(options, github.text_search);
+  let (pulls, next, total_pages) = match search.is_empty() {
+    true => {
+      let mut pulls = fetch_pulls(github, owner, repo, query.state, number).await?;
+      let (next, total_pages) = next_page(&pulls);
+      let pulls = pulls.take_items();
+      let done = !pulls.iter().all(|p| query.changed(p.updated_at));
+
+      // incremental fetches page one at a time, as they stop well before the last page
+      match query.since {
+        Some(_) => (pulls, if done { None } else { next }, None),
+        None => (pulls, next, total_pages),
+      }
+    }
+    false => {
+      search.time("updated", query.since, None);
+
+      // search results are issues, without the fields of pull requests
+      let (found, next, total_pages): (Vec<SearchedPull>, _, _) = search
+        .fetch(github, owner, repo, &query.qualifiers(), number)
+        .await?;
+      let pulls: Vec<GitHubPull> = stream::iter(found.iter())
+        .map(|p| fetch_pull(github, owner, repo, p.number))
+        .buffered(DETAILS_CONCURRENCY)
+        .try_collect()
+        .await?;
+      (pulls, next, total_pages)
+    }
+  };
+
+  let pulls: Vec<GitHubPull> = pulls
+    .into_iter()
+    .filter(|p| {
+      let labels = p.labels.iter().map(|l| l.name.as_str());
+      query.matches(&p.user.login, labels, p.created_at, p.updated_at)
+    })
+    .collect();
+
+  let requested = |columns: &[&str]| columns.iter().any(|c| column_requested(options, c));
+  let details: Vec<Option<PullDetails>> = match requested(DETAIL_COLUMNS) {
+    true => {
+      stream::iter(pulls.iter())
+        .map(|p| fetch_pull::<PullDetails>(github, owner, repo, p.number))
+        .buffered(DETAILS_CONCURRENCY)
+        .map_ok(Some)
+        .try_collect()
+        .await?
+    }
+    false => pulls.iter().map(|_| None).collect(),
+  };
+  let reviews: Vec<Option<Vec<GitHubReview>>> = match requested(REVIEW_COLUMNS) {
+    true => {
+      stream::iter(pulls.iter())
+        .map(|p| fetch_reviews(github, owner, repo, p.number))
+        .buffered(DETAILS_CONCURRENCY)
+        .map_ok(Some)
+        .try_collect()
+        .await?
+    }
+    false => pulls.iter().map(|_| None).collect(),
+  };
+
+  let rows = pulls
+    .into_iter()
+    .zip(details)
+    .zip(reviews)
+    .map(|((r, details), reviews)| {
+      let reviews = reviews.map(|reviews| {
+        let reviews = reviews
+          .into_iter()
+          .map(|v| (v.user.map(|u| u.login), v.submitted_at));
+        summarize_reviews(&r.user.login, reviews)
+      });
+
+      PullRequest {
+        id: r.id,
+        number: r.number,
+        created_at: r.created_at,
+        updated_at: r.updated_at,
+        closed_at: r.closed_at,
+        merged_at: r.merged_at,
         title: r.title,
         body: r.body,
-        url: r.url.to_string(),
+        url: r.url,
         author: r.user.login,
-        state: match r.state {
-          IssueState::Open => "open".to_owned(),
-          IssueState::Closed => "closed".to_owned(),
-          _ => "unknown".to_owned(),
+        state: r.state,
+        draft: r.draft,
+        labels: r.labels.into_iter().map(|l| l.name).collect(),
+        requested_reviewers: r.requested_reviewers.into_iter().map(|a| a.login).collect(),
+        base_branch: r.base.name,
+        head_branch: r.head.name,
+        merge_commit_sha: r.merge_commit_sha,
+        additions: details.as_ref().map(|d| d.additions),
+        deletions: details.as_ref().map(|d| d.deletions),
+        changed_files: details.as_ref().map(|d| d.changed_files),
+        commits: details.as_ref().map(|d| d.commits),
+        first_review_at: reviews.as_ref().and_then(|(_, _, first)| *first),
+        reviews: reviews.as_ref().map(|(_, count, _)| *count),
+        reviewers: reviews.map(|(reviewers, _, _)| reviewers),
+        label: query.label.clone(),
+        owner: owner.clone(),
+        repo: repo.clone(),
+        archived: query.archived,
+      }
+    })
+    .collect();
+
+  Ok(Page {
+    rows,
+    next,
+    total_pages,
+  })
+}
+
+/// A page of pull requests from the GraphQL api, which reads their details and reviews in the
+/// same query, and only the fields of the columns the query reads.
+async fn fetch_graphql_page(
+  github: &GitHub,
+  options: &HashMap<String, String>,
+  query: &PullQuery,
+  after: Option<String>,
+) -> Result<Page<PullRequest>> {
+  let (owner, repo) = (&query.owner, &query.repo);
+  let mut selection = Selection::default();
+  selection.field(FILTER_FIELDS);
+  if query.label.is_some() {
+    selection.field(LABELS_FIELD);
+  }
+  selection.columns(options, PULL_FIELDS);
+
+  let mut search = query.search(options, github.text_search);
+  let pulls: Nodes<PullNode> = match search.is_empty() {
+    true => {
+      let states = match query.state {
+        "open" => json!(["OPEN"]),
+        "closed" => json!(["CLOSED", "MERGED"]),
+        _ => serde_json::Value::Null,
+      };
+      let graphql = PULLS_QUERY.replace("{fields}", &selection.to_string());
+      let variables = json!({ "owner": owner, "repo": repo, "states": states, "after": after });
+      repository_page(github, &graphql, variables).await?
+    }
+    false => {
+      search.time("updated", query.since, None);
+      let mut qualifiers = query.qualifiers();
+      qualifiers.push("sort:updated-desc");
+      let search = search.query(owner, repo, &qualifiers);
+      search_page(github, search, "PullRequest", &selection, after).await?
+    }
+  };
+
+  // pull requests are listed most recently updated first
+  let next = match pulls.nodes.iter().all(|p| query.changed(p.updated_at)) {
+    true => pulls.next(),
+    false => None,
+  };
+
+  let rows = pulls
+    .nodes
+    .into_iter()
+    .map(|p| {
+      // github shows deleted accounts as ghost
+      let author = p.author.map_or_else(|| "ghost".to_owned(), |a| a.login);
+      let labels: Vec<String> = p.labels.map_or_else(Vec::new, |labels| {
+        labels.nodes.into_iter().map(|l| l.name).collect()
+      });
+      (p, author, labels)
+    })
+    .filter(|(p, author, labels)| {
+      let labels = labels.iter().map(String::as_str);
+      query.matches(author, labels, p.created_at, p.updated_at)
+    })
+    .map(|(p, author, labels)| {
+      let reviews = p.reviews.map(|reviews| {
+        let reviews = reviews
+          .nodes
+          .into_iter()
+          .map(|v| (v.author.map(|a| a.login), v.submitted_at));
+        summarize_reviews(&author, reviews)
+      });
+
+      PullRequest {
+        id: p.database_id,
+        number: p.number,
+        created_at: p.created_at,
+        updated_at: p.updated_at,
+        closed_at: p.closed_at,
+        merged_at: p.merged_at,
+        title: p.title,
+        body: p.body,
+        // the rest api url, as the rest backend reads it
+        url: format!(
+          "{}repos/{}/{}/pulls/{}",
+          github.rest_url, owner, repo, p.number
+        ),
+        author,
+        // merged pull requests are closed ones to the rest api
+        state: match p.state.as_str() {
+          "MERGED" => "closed".to_owned(),
+          state => state.to_lowercase(),
         },
-        owner: options.get("owner").unwrap_or(&"".to_owned()).clone(),
-        repo: options.get("repo").unwrap_or(&"".to_owned()).clone(),
-      })
-      .flat_map(|r| {
-        let mut bytes = serde_json::to_vec(&r).unwrap();
-        bytes.push(b'\n');
-        bytes
-      })
-      .collect();
-
-    Ok(Cursor::new(pulls))
+        draft: p.is_draft,
+        labels,
+        requested_reviewers: p.review_requests.map_or_else(Vec::new, |requests| {
+          requests
+            .nodes
+            .into_iter()
+            .filter_map(|r| r.requested_reviewer.and_then(|r| r.login))
+            .collect()
+        }),
+        base_branch: p.base_ref_name,
+        head_branch: p.head_ref_name,
+        // only merged pull requests have a merge commit
+        merge_commit_sha: p.merge_commit.map(|c| c.oid),
+        additions: p.additions,
+        deletions: p.deletions,
+        changed_files: p.changed_files,
+        commits: p.commits.map(|c| c.total_count),
+        first_review_at: reviews.as_ref().and_then(|(_, _, first)| *first),
+        reviews: reviews.as_ref().map(|(_, count, _)| *count),
+        reviewers: reviews.map(|(reviewers, _, _)| reviewers),
+        label: query.label.clone(),
+        owner: owner.clone(),
+        repo: repo.clone(),
+        archived: query.archived,
+      }
+    })
+    .collect();
+
+  Ok(Page {
+    rows,
+    next,
+    total_pages: None,
+  })
+}
+
+#[derive(Debug, Clone)]
+pub struct PullRequestTable {
+  clients: Clients,
+}
+
+impl PullRequestTable {
+  pub fn new(clients: Clients) -> Self {
+    Self { clients }
+  }
+}
+
+/// Pull requests are listed most recently updated first, so paging stops at the first page with
+/// one that wasn't updated since `since`. Queries with `LIKE` filters search for pull requests
+/// instead, if the datasource enables text search.
+#[async_trait]
+impl PagedDataSource for PullRequestTable {
+  type Row = PullRequest;
+
+  fn schema(&self) -> SchemaRef {
+    PullRequest::table_schema()
+  }
+
+  fn capabilities(&self) -> TableCapabilities {
+    TableCapabilities {
+      projection: true,
+      ..PullRequest::capabilities()
+    }
+  }
+
+  async fn fetch_page(
+    &self,
+    options: &HashMap<String, String>,
+    _time_range: Option<TimeRange>,
+    page: PageToken,
+  ) -> Result<Page<Self::Row>> {
+    let query = match PullQuery::new(options)? {
+      Some(query) => query,
+      None => {
+        return Ok(Page {
+          rows: vec![],
+          next: None,
+          total_pages: None,
+        })
+      }
+    };
+
+    let github = self.clients.current().await?;
+    match github.api {
+      Api::Rest => fetch_rest_page(&github, options, &query, page_number(&page)?).await,
+      Api::GraphQl => fetch_graphql_page(&github, options, &query, cursor(&page)?).await,
+    }
   }
 }
@@ -1,134 +1,202 @@
-use std::collections::{BTreeMap, HashMap};
-use std::io::Cursor;
-use std::sync::Arc;
+use std::collections::HashMap;
 
 use anyhow::Result;
-use arrow::datatypes::{DataType, Field, Schema, SchemaRef, TimeUnit};
+use arrow::datatypes::SchemaRef;
 use async_trait::async_trait;
-use cached::proc_macro::cached;
-use grafana_plugin_sdk::DataSource;
+use chrono::{DateTime, Utc};
+use grafana_plugin_sdk::{
+  GrafanaTable, Page, PageToken, PagedDataSource, TableCapabilities, TableSchema, TimeRange,
+};
 use octocrab::models::repos::Release as GitHubRelease;
-use serde::Serialize;
+use octocrab::Page as GitHubPage;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
 
-#[derive(Serialize, Debug, Clone)]
+use super::client::{Api, GitHub};
+use super::graphql::{cursor, repository_page, Nodes, Selection};
+use super::{next_page, page_number, Account, Clients};
+
+#[derive(Serialize, GrafanaTable, Debug, Clone)]
 pub struct Release {
-  created_at: i64,
-  published_at: i64,
+  created_at: DateTime<Utc>,
+  #[table(time)]
+  published_at: DateTime<Utc>,
   name: Option<String>,
   body: Option<String>,
   url: String,
   tag_name: String,
   draft: bool,
   pre_release: bool,
+  author: String,
+  // these fields are passed to the github api as filters
+  #[table(required)]
   owner: String,
+  // queries without a repo filter read every repo of the owner
+  #[table(filter)]
   repo: String,
-  author: String,
+  #[table(filter)]
+  archived: bool,
 }
 
-#[cached(time = 600, result = true)]
-async fn cached_fetch(owner: String, repo: String) -> Result<Vec<GitHubRelease>> {
-  let octocrab = octocrab::instance();
-
-  let mut current_page = octocrab
-    .repos(owner, repo)
-    .releases()
-    .list()
-    .per_page(100)
-    .send()
-    .await?;
-
-  let mut releases = current_page.take_items();
-
-  while let Some(mut page) = octocrab
-    .get_page::<GitHubRelease>(&current_page.next)
-    .await?
-  {
-    releases.extend(page.take_items());
-    current_page = page;
+/// A release from the GraphQL api, with only the fields of the columns a query reads.
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct ReleaseNode {
+  database_id: u64,
+  created_at: DateTime<Utc>,
+  // null for drafts
+  published_at: Option<DateTime<Utc>>,
+  name: Option<String>,
+  description: Option<String>,
+  #[serde(default)]
+  tag_name: String,
+  #[serde(default)]
+  is_draft: bool,
+  #[serde(default)]
+  is_prerelease: bool,
+  author: Option<Account>,
+}
+
+const RELEASES_QUERY: &str = "query($owner: String!, $repo: String!, $after: String) {
+  rateLimit { cost remaining resetAt }
+  repository(owner: $owner, name: $repo) {
+    page: releases(first: 100, after: $after, orderBy: {field: CREATED_AT, direction: DESC}) {
+      pageInfo { hasNextPage endCursor }
+      nodes { {fields} }
+    }
   }
+}";
+
+// the fields of a release that each column is read from
+const RELEASE_FIELDS: &[(&str, &str)] = &[
+  ("name", "name"),
+  ("body", "description"),
+  ("tag_name", "tagName"),
+  ("draft", "isDraft"),
+  ("pre_release", "isPrerelease"),
+  ("author", "author { login }"),
+];
 
-  Ok(releases)
+#[derive(Debug, Clone)]
+pub struct ReleaseTable {
+  clients: Clients,
 }
 
-#[derive(Debug, Clone)]
-pub struct ReleaseTable;
+impl ReleaseTable {
+  pub fn new(clients: Clients) -> Self {
+    Self { clients }
+  }
+}
 
 #[async_trait]
-impl DataSource for ReleaseTable {
-  type Data = Cursor<Vec<u8>>;
+impl PagedDataSource for ReleaseTable {
+  type Row = Release;
 
   fn schema(&self) -> SchemaRef {
-    let mut metadata = BTreeMap::new();
-    metadata.insert("filter".to_owned(), "true".to_owned());
-
-    let created_at = Field::new(
-      "created_at",
-      DataType::Timestamp(TimeUnit::Nanosecond, None),
-      false,
-    );
-    let published_at = Field::new(
-      "published_at",
-      DataType::Timestamp(TimeUnit::Nanosecond, None),
-      false,
-    );
-    let name = Field::new("name", DataType::Utf8, true);
-    let body = Field::new("body", DataType::Utf8, true);
-    let url = Field::new("url", DataType::Utf8, false);
-    let tag_name = Field::new("tag_name", DataType::Utf8, false);
-    let draft = Field::new("draft", DataType::Boolean, false);
-    let pre_release = Field::new("pre_release", DataType::Boolean, false);
-    let author = Field::new("author", DataType::Utf8, false);
-
-    // these fields are passed to the github api as filters
-    let mut owner = Field::new("owner", DataType::Utf8, false);
-    owner.set_metadata(Some(metadata.clone()));
-    let mut repo = Field::new("repo", DataType::Utf8, false);
-    repo.set_metadata(Some(metadata.clone()));
-
-    let schema = Schema::new(vec![
-      created_at,
-      published_at,
-      name,
-      body,
-      url,
-      tag_name,
-      draft,
-      pre_release,
-      author,
-      owner,
-      repo,
-    ]);
-
-    Arc::new(schema)
+    Release::table_schema()
+  }
+
+  fn capabilities(&self) -> TableCapabilities {
+    TableCapabilities {
+      projection: true,
+      ..Release::capabilities()
+    }
   }
 
-  async fn fetch_results(&self, options: HashMap<String, String>) -> Result<Self::Data> {
+  async fn fetch_page(
+    &self,
+    options: &HashMap<String, String>,
+    _time_range: Option<TimeRange>,
+    page: PageToken,
+  ) -> Result<Page<Self::Row>> {
     let owner = options.get("owner").unwrap_or(&"".to_owned()).clone();
     let repo = options.get("repo").unwrap_or(&"".to_owned()).clone();
-    let releases = cached_fetch(owner, repo).await?;
-
-    let results: Vec<u8> = releases
+    // set by `Repos` for every repo
+    let archived = options.get("archived").map_or(Ok(false), |a| a.parse())?;
+    let github = self.clients.current().await?;
+    if github.api == Api::GraphQl {
+      return fetch_graphql_page(&github, options, &owner, &repo, archived, cursor(&page)?).await;
+    }
+
+    let route = format!("/repos/{}/{}/releases", owner, repo);
+    let params = [("per_page", 100), ("page", page_number(&page)?)];
+    let mut releases: GitHubPage<GitHubRelease> =
+      github.call(|| github.get(&route, Some(&params))).await?;
+    let (next, total_pages) = next_page(&releases);
+
+    let rows = releases
+      .take_items()
       .into_iter()
       .map(|r| Release {
-        created_at: r.created_at.timestamp() * 1000 * 1000 * 1000,
-        published_at: r.published_at.timestamp() * 1000 * 1000 * 1000,
+        created_at: r.created_at,
+        published_at: r.published_at,
         name: r.name,
         body: r.body,
         url: r.url.to_string(),
         tag_name: r.tag_name,
         draft: r.draft,
         pre_release: r.prerelease,
-        owner: options.get("owner").unwrap_or(&"".to_owned()).clone(),
-        repo: options.get("repo").unwrap_or(&"".to_owned()).clone(),
+        owner: owner.clone(),
+        repo: repo.clone(),
+        archived,
         author: r.author.login,
       })
-      .flat_map(|r| {
-        let mut bytes = serde_json::to_vec(&r).unwrap();
-        bytes.push(b'\n');
-        bytes
-      })
       .collect();
 
-    Ok(Cursor::new(results))
+    Ok(Page {
+      rows,
+      next,
+      total_pages,
+    })
   }
 }
+
+/// A page of releases from the GraphQL api, with only the fields of the columns the query reads.
+async fn fetch_graphql_page(
+  github: &GitHub,
+  options: &HashMap<String, String>,
+  owner: &str,
+  repo: &str,
+  archived: bool,
+  after: Option<String>,
+) -> Result<Page<Release>> {
+  let mut selection = Selection::default();
+  selection.field("databaseId createdAt publishedAt");
+  selection.columns(options, RELEASE_FIELDS);
+
+  let query = RELEASES_QUERY.replace("{fields}", &selection.to_string());
+  let variables = json!({ "owner": owner, "repo": repo, "after": after });
+  let releases: Nodes<ReleaseNode> = repository_page(github, &query, variables).await?;
+  let next = releases.next();
+
+  let rows = releases
+    .nodes
+    .into_iter()
+    .map(|r| Release {
+      created_at: r.created_at,
+      // drafts are placed at their creation
+      published_at: r.published_at.unwrap_or(r.created_at),
+      name: r.name,
+      body: r.description,
+      // the rest api url, as the rest backend reads it
+      url: format!(
+        "{}repos/{}/{}/releases/{}",
+        github.rest_url, owner, repo, r.database_id
+      ),
+      tag_name: r.tag_name,
+      draft: r.is_draft,
+      pre_release: r.is_prerelease,
+      owner: owner.to_owned(),
+      repo: repo.to_owned(),
+      archived,
+      // github shows deleted accounts as ghost
+      author: r.author.map_or_else(|| "ghost".to_owned(), |a| a.login),
+    })
+    .collect();
+
+  Ok(Page {
+    rows,
+    next,
+    total_pages: None,
+  })
+}
@@ -0,0 +1,231 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+use arrow::datatypes::SchemaRef;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use grafana_plugin_sdk::{
+  GrafanaTable, Page, PageToken, PagedDataSource, Partitioner, TableCapabilities, TableSchema,
+  TimeRange,
+};
+use octocrab::Page as GitHubPage;
+use serde::{Deserialize, Serialize};
+
+use super::client::GitHub;
+use super::{next_page, page_number, Clients};
+
+#[derive(Serialize, GrafanaTable, Debug, Clone)]
+pub struct Repository {
+  name: String,
+  full_name: String,
+  description: Option<String>,
+  visibility: String,
+  fork: bool,
+  #[table(filter)]
+  archived: bool,
+  stars: u64,
+  forks: u64,
+  open_issues: u64,
+  language: Option<String>,
+  default_branch: Option<String>,
+  created_at: Option<DateTime<Utc>>,
+  pushed_at: Option<DateTime<Utc>>,
+  url: String,
+  // these fields are passed to the github api as filters
+  #[table(required)]
+  owner: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct GitHubRepo {
+  name: String,
+  full_name: String,
+  description: Option<String>,
+  // older servers only have `private`
+  #[serde(default)]
+  visibility: Option<String>,
+  private: bool,
+  fork: bool,
+  #[serde(default)]
+  archived: bool,
+  stargazers_count: u64,
+  forks_count: u64,
+  open_issues_count: u64,
+  language: Option<String>,
+  default_branch: Option<String>,
+  created_at: Option<DateTime<Utc>>,
+  pushed_at: Option<DateTime<Utc>>,
+  html_url: String,
+}
+
+#[derive(Serialize)]
+struct RepoParams<'a> {
+  #[serde(rename = "type")]
+  kind: &'a str,
+  per_page: u8,
+  page: u32,
+}
+
+/// A page of the repos of an organization or, if there is none called `owner`, of a user.
+async fn fetch_repos(github: &GitHub, owner: &str, page: u32) -> Result<GitHubPage<GitHubRepo>> {
+  let route = format!("/orgs/{}/repos", owner);
+  let params = RepoParams {
+    kind: "all",
+    per_page: 100,
+    page,
+  };
+  let e = match github.call(|| github.get(&route, Some(&params))).await {
+    Ok(repos) => return Ok(repos),
+    Err(e) => e,
+  };
+
+  match e.downcast_ref::<octocrab::Error>() {
+    Some(octocrab::Error::GitHub { source, .. }) if source.message == "Not Found" => {
+      let route = format!("/users/{}/repos", owner);
+      let params = RepoParams {
+        kind: "owner",
+        ..params
+      };
+      github.call(|| github.get(&route, Some(&params))).await
+    }
+    _ => Err(e),
+  }
+}
+
+fn archived_filter(options: &HashMap<String, String>) -> Result<Option<bool>> {
+  match options.get("archived") {
+    Some(archived) => Ok(Some(archived.parse()?)),
+    None => Ok(None),
+  }
+}
+
+#[derive(Debug, Clone)]
+pub struct RepositoryTable {
+  clients: Clients,
+}
+
+impl RepositoryTable {
+  pub fn new(clients: Clients) -> Self {
+    Self { clients }
+  }
+}
+
+#[async_trait]
+impl PagedDataSource for RepositoryTable {
+  type Row = Repository;
+
+  fn schema(&self) -> SchemaRef {
+    Repository::table_schema()
+  }
+
+  fn capabilities(&self) -> TableCapabilities {
+    Repository::capabilities()
+  }
+
+  async fn fetch_page(
+    &self,
+    options: &HashMap<String, String>,
+    _time_range: Option<TimeRange>,
+    page: PageToken,
+  ) -> Result<Page<Self::Row>> {
+    let owner = options.get("owner").unwrap_or(&"".to_owned()).clone();
+    let archived = archived_filter(options)?;
+    let github = self.clients.current().await?;
+    let mut repos = fetch_repos(&github, &owner, page_number(&page)?).await?;
+    let (next, total_pages) = next_page(&repos);
+
+    // the api can't filter by archived
+    let rows = repos
+      .take_items()
+      .into_iter()
+      .filter(|r| archived.map_or(true, |a| a == r.archived))
+      .map(|r| Repository {
+        visibility: match (r.visibility, r.private) {
+          (Some(visibility), _) => visibility,
+          (None, true) => "private".to_owned(),
+          (None, false) => "public".to_owned(),
+        },
+        name: r.name,
+        full_name: r.full_name,
+        description: r.description,
+        fork: r.fork,
+        archived: r.archived,
+        stars: r.stargazers_count,
+        forks: r.forks_count,
+        open_issues: r.open_issues_count,
+        language: r.language,
+        default_branch: r.default_branch,
+        created_at: r.created_at,
+        pushed_at: r.pushed_at,
+        url: r.html_url,
+        owner: owner.clone(),
+      })
+      .collect();
+
+    Ok(Page {
+      rows,
+      next,
+      total_pages,
+    })
+  }
+}
+
+/// Splits queries without a repo filter into a query for each repo of the owner.
+///
+/// Every query is passed whether its repo is archived in the `archived` option, and repos are
+/// left out if they don't match an `archived` filter.
+#[derive(Debug, Clone)]
+pub struct Repos {
+  clients: Clients,
+}
+
+impl Repos {
+  pub fn new(clients: Clients) -> Self {
+    Self { clients }
+  }
+}
+
+#[async_trait]
+impl Partitioner for Repos {
+  async fn partitions(
+    &self,
+    options: &HashMap<String, String>,
+  ) -> Result<Vec<HashMap<String, String>>> {
+    let owner = options.get("owner").unwrap_or(&"".to_owned()).clone();
+    let archived = archived_filter(options)?;
+    let github = self.clients.current().await?;
+
+    let mut repos: Vec<GitHubRepo> = vec![];
+    match options.get("repo") {
+      Some(repo) => {
+        let route = format!("/repos/{}/{}", owner, repo);
+        let repo = github.call(|| github.get(&route, None::<&()>)).await?;
+        repos.push(repo);
+      }
+      None => {
+        for page in 1.. {
+          let mut page = fetch_repos(&github, &owner, page).await?;
+          let last = page.next.is_none();
+          repos.extend(page.take_items());
+
+          if last {
+            break;
+          }
+        }
+      }
+    }
+
+    Ok(
+      repos
+        .into_iter()
+        .filter(|r| archived.map_or(true, |a| a == r.archived))
+        .map(|r| {
+          let mut options = options.clone();
+          options.insert("repo".to_owned(), r.name);
+          options.insert("archived".to_owned(), r.archived.to_string());
+          options
+        })
+        .collect(),
+    )
+  }
+}
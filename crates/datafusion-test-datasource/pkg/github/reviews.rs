@@ -0,0 +1,185 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+use arrow::datatypes::SchemaRef;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use futures::stream::{self, StreamExt, TryStreamExt};
+use grafana_plugin_sdk::{
+  GrafanaTable, Page, PageToken, PagedDataSource, TableCapabilities, TableSchema, TimeRange,
+};
+use octocrab::Page as GitHubPage;
+use serde::{Deserialize, Serialize};
+
+use super::client::GitHub;
+use super::pull_requests::{fetch_pull, fetch_pulls, GitHubPull};
+use super::{next_page, page_number, Account, Clients};
+
+// the most pull requests whose reviews are fetched at once
+const REVIEWS_CONCURRENCY: usize = 4;
+
+#[derive(Serialize, GrafanaTable, Debug, Clone)]
+pub struct PullRequestReview {
+  id: u64,
+  #[table(filter)]
+  pull_request: u64,
+  pull_request_author: String,
+  pull_request_created_at: DateTime<Utc>,
+  // null for deleted accounts
+  reviewer: Option<String>,
+  state: String,
+  // null for pending reviews
+  #[table(time)]
+  submitted_at: Option<DateTime<Utc>>,
+  commit_id: Option<String>,
+  url: String,
+  // these fields are passed to the github api as filters
+  #[table(required)]
+  owner: String,
+  #[table(required)]
+  repo: String,
+}
+
+#[derive(Deserialize, Debug)]
+pub(super) struct GitHubReview {
+  id: u64,
+  pub(super) user: Option<Account>,
+  state: String,
+  pub(super) submitted_at: Option<DateTime<Utc>>,
+  commit_id: Option<String>,
+  html_url: String,
+}
+
+#[derive(Serialize)]
+struct ReviewParams {
+  per_page: u8,
+  page: u32,
+}
+
+/// Every review of a pull request, oldest first.
+pub(super) async fn fetch_reviews(
+  github: &GitHub,
+  owner: &str,
+  repo: &str,
+  number: u64,
+) -> Result<Vec<GitHubReview>> {
+  let route = format!("/repos/{}/{}/pulls/{}/reviews", owner, repo, number);
+  let mut reviews = vec![];
+
+  for page in 1.. {
+    let params = ReviewParams {
+      per_page: 100,
+      page,
+    };
+    let mut response: GitHubPage<GitHubReview> =
+      github.call(|| github.get(&route, Some(&params))).await?;
+    let last = response.next.is_none();
+    reviews.extend(response.take_items());
+
+    if last {
+      break;
+    }
+  }
+
+  Ok(reviews)
+}
+
+/// Lists the reviews of a page of pull requests at a time, or of a single pull request.
+#[derive(Debug, Clone)]
+pub struct PullRequestReviewTable {
+  clients: Clients,
+}
+
+impl PullRequestReviewTable {
+  pub fn new(clients: Clients) -> Self {
+    Self { clients }
+  }
+}
+
+#[async_trait]
+impl PagedDataSource for PullRequestReviewTable {
+  type Row = PullRequestReview;
+
+  fn schema(&self) -> SchemaRef {
+    PullRequestReview::table_schema()
+  }
+
+  fn capabilities(&self) -> TableCapabilities {
+    PullRequestReview::capabilities()
+  }
+
+  async fn fetch_page(
+    &self,
+    options: &HashMap<String, String>,
+    time_range: Option<TimeRange>,
+    page: PageToken,
+  ) -> Result<Page<Self::Row>> {
+    let owner = options.get("owner").unwrap_or(&"".to_owned()).clone();
+    let repo = options.get("repo").unwrap_or(&"".to_owned()).clone();
+    let number: Option<u64> = match options.get("pull_request") {
+      Some(number) => Some(number.parse()?),
+      None => None,
+    };
+
+    let page = page_number(&page)?;
+    let github = self.clients.current().await?;
+    let (pulls, next, total_pages) = match number {
+      Some(number) => {
+        let pull: GitHubPull = fetch_pull(&github, &owner, &repo, number).await?;
+        (vec![pull], None, None)
+      }
+      None => {
+        let mut pulls = fetch_pulls(&github, &owner, &repo, "all", page).await?;
+        let (next, total_pages) = next_page(&pulls);
+
+        // reviews update their pull request, so pull requests that weren't updated since the
+        // start of the time range have no reviews in it, and neither do the ones after them
+        let updated = |p: &GitHubPull| match (time_range, p.updated_at) {
+          (Some(range), Some(updated_at)) => updated_at >= range.from,
+          _ => true,
+        };
+        let pulls = pulls.take_items();
+        let done = !pulls.iter().all(updated);
+        let pulls: Vec<GitHubPull> = pulls.into_iter().filter(updated).collect();
+
+        match time_range {
+          Some(_) => (pulls, if done { None } else { next }, None),
+          None => (pulls, next, total_pages),
+        }
+      }
+    };
+
+    let reviews: Vec<Vec<GitHubReview>> = stream::iter(pulls.iter())
+      .map(|p| fetch_reviews(&github, &owner, &repo, p.number))
+      .buffered(REVIEWS_CONCURRENCY)
+      .try_collect()
+      .await?;
+
+    let rows = pulls
+      .iter()
+      .zip(reviews)
+      .flat_map(|(p, reviews)| {
+        let (owner, repo) = (&owner, &repo);
+        reviews.into_iter().map(move |r| PullRequestReview {
+          id: r.id,
+          pull_request: p.number,
+          pull_request_author: p.user.login.clone(),
+          pull_request_created_at: p.created_at,
+          reviewer: r.user.map(|u| u.login),
+          state: r.state,
+          submitted_at: r.submitted_at,
+          commit_id: r.commit_id,
+          url: r.html_url,
+          owner: owner.clone(),
+          repo: repo.clone(),
+        })
+      })
+      .collect();
+
+    Ok(Page {
+      rows,
+      next,
+      total_pages,
+    })
+  }
+}
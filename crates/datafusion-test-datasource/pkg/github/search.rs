@@ -0,0 +1,147 @@
+use std::collections::HashMap;
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use grafana_plugin_sdk::{FilterOperator, PageToken};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use super::client::GitHub;
+use super::time_qualifier;
+
+const PER_PAGE: u32 = 100;
+
+// the search api only returns the first 1000 results of a search
+const MAX_RESULTS: u32 = 1000;
+
+/// A search of the issues and pull requests of a repo, for the `LIKE` filters that listing them
+/// can't apply.
+///
+/// `LIKE` patterns are searched for as phrases, which github matches on whole words and
+/// regardless of case. Rows that only contain a pattern inside a word aren't found, so text is
+/// only searched for when the datasource enables it.
+#[derive(Debug, Default)]
+pub(super) struct Search {
+  phrases: Vec<String>,
+  fields: Vec<&'static str>,
+  qualifiers: Vec<String>,
+}
+
+impl Search {
+  /// Searches the phrases of a `LIKE` filter on `column`.
+  pub(super) fn like(&mut self, options: &HashMap<String, String>, column: &'static str) {
+    let pattern = match options.get(&FilterOperator::Like.option_key(column)) {
+      Some(p) => p,
+      None => return,
+    };
+
+    let phrases: Vec<String> = pattern
+      .split(|c| c == '%' || c == '_')
+      .map(|p| p.replace('"', " ").trim().to_owned())
+      .filter(|p| !p.is_empty())
+      .collect();
+
+    // patterns of wildcards only match everything
+    if !phrases.is_empty() {
+      self.phrases.extend(phrases);
+      self.fields.push(column);
+    }
+  }
+
+  /// A qualifier that matches a value exactly, e.g. `label:"good first issue"`.
+  pub(super) fn qualifier(&mut self, name: &str, value: &str) {
+    let value = value.replace('"', "");
+    self.qualifiers.push(format!("{}:\"{}\"", name, value));
+  }
+
+  /// A qualifier that matches a time range, e.g. `created:>=2021-06-01T00:00:00Z`.
+  pub(super) fn time(
+    &mut self,
+    name: &str,
+    since: Option<DateTime<Utc>>,
+    until: Option<DateTime<Utc>>,
+  ) {
+    if let Some(range) = time_qualifier(since, until) {
+      self.qualifiers.push(format!("{}:{}", name, range));
+    }
+  }
+
+  /// Whether there is anything to search for.
+  pub(super) fn is_empty(&self) -> bool {
+    self.phrases.is_empty() && self.qualifiers.is_empty()
+  }
+
+  /// The search query, with `extra` qualifiers such as `is:pr`.
+  pub(super) fn query(&self, owner: &str, repo: &str, extra: &[&str]) -> String {
+    let mut query = vec![format!("repo:{}/{}", owner, repo)];
+    query.extend(self.phrases.iter().map(|p| format!("\"{}\"", p)));
+    if !self.fields.is_empty() {
+      query.push(format!("in:{}", self.fields.join(",")));
+    }
+    query.extend(self.qualifiers.iter().cloned());
+    query.extend(extra.iter().map(|q| q.to_string()));
+    query.join(" ")
+  }
+
+  /// A page of the issues and pull requests that match, most recently updated first, with
+  /// `extra` qualifiers such as `is:pr`.
+  pub(super) async fn fetch<T: DeserializeOwned>(
+    &self,
+    github: &GitHub,
+    owner: &str,
+    repo: &str,
+    extra: &[&str],
+    page: u32,
+  ) -> Result<(Vec<T>, Option<PageToken>, Option<u32>)> {
+    let query = self.query(owner, repo, extra);
+    let params = SearchParams {
+      q: &query,
+      sort: "updated",
+      order: "desc",
+      per_page: PER_PAGE,
+      page,
+    };
+    let results: SearchResults<T> = github
+      .call(|| github.get("/search/issues", Some(&params)))
+      .await?;
+
+    check_total_count(&query, results.total_count)?;
+    let pages = (results.total_count + PER_PAGE - 1) / PER_PAGE;
+    match page < pages {
+      true => Ok((
+        results.items,
+        Some(PageToken::Number(page + 1)),
+        Some(pages),
+      )),
+      false => Ok((results.items, None, Some(pages))),
+    }
+  }
+}
+
+/// Fails searches that find more results than github returns, rather than leaving the rest out.
+pub(super) fn check_total_count(query: &str, total_count: u32) -> Result<()> {
+  match total_count > MAX_RESULTS {
+    true => Err(anyhow!(
+      "github search {:?} found {} results, only the first {} can be read, narrow the filters",
+      query,
+      total_count,
+      MAX_RESULTS
+    )),
+    false => Ok(()),
+  }
+}
+
+#[derive(Serialize)]
+struct SearchParams<'a> {
+  q: &'a str,
+  sort: &'a str,
+  order: &'a str,
+  per_page: u32,
+  page: u32,
+}
+
+#[derive(Deserialize, Debug)]
+struct SearchResults<T> {
+  total_count: u32,
+  items: Vec<T>,
+}
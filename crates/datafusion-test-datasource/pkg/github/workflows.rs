@@ -0,0 +1,527 @@
+use std::collections::HashMap;
+
+use anyhow::{anyhow, Result};
+use arrow::datatypes::SchemaRef;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use futures::stream::{self, StreamExt, TryStreamExt};
+use grafana_plugin_sdk::{
+  FilterOperator, GrafanaTable, Page, PageToken, PagedDataSource, TableCapabilities, TableSchema,
+  TimeRange,
+};
+use serde::{Deserialize, Serialize};
+
+use super::client::GitHub;
+use super::{page_number, parse_time, time_qualifier, Account, Clients};
+
+const PER_PAGE: u32 = 100;
+
+// the most runs the api lists when they are filtered by branch, status or creation
+const MAX_FILTERED_RUNS: u32 = 1000;
+
+// the most runs whose jobs are fetched at once
+const JOBS_CONCURRENCY: usize = 4;
+
+// the values of the status filter of the runs api, which takes both statuses and conclusions
+const STATUSES: &[&str] = &[
+  "queued",
+  "in_progress",
+  "completed",
+  "waiting",
+  "requested",
+  "pending",
+];
+const CONCLUSIONS: &[&str] = &[
+  "success",
+  "failure",
+  "neutral",
+  "cancelled",
+  "skipped",
+  "timed_out",
+  "action_required",
+  "stale",
+  "startup_failure",
+];
+
+#[derive(Serialize, GrafanaTable, Debug, Clone)]
+pub struct WorkflowRun {
+  id: u64,
+  run_number: u64,
+  run_attempt: Option<u32>,
+  workflow_name: String,
+  workflow_id: u64,
+  event: String,
+  #[table(filter)]
+  branch: Option<String>,
+  head_sha: String,
+  #[table(filter)]
+  status: String,
+  #[table(filter)]
+  conclusion: Option<String>,
+  #[table(time, filter(gt_eq, lt_eq))]
+  created_at: DateTime<Utc>,
+  started_at: Option<DateTime<Utc>>,
+  completed_at: Option<DateTime<Utc>>,
+  duration_seconds: Option<i64>,
+  actor: Option<String>,
+  url: String,
+  // these fields are passed to the github api as filters
+  #[table(filter)]
+  workflow: Option<String>,
+  #[table(required)]
+  owner: String,
+  #[table(required)]
+  repo: String,
+}
+
+#[derive(Serialize, GrafanaTable, Debug, Clone)]
+pub struct WorkflowJob {
+  id: u64,
+  #[table(filter)]
+  run_id: u64,
+  run_attempt: Option<u32>,
+  name: String,
+  workflow_name: String,
+  event: String,
+  #[table(filter)]
+  branch: Option<String>,
+  status: String,
+  conclusion: Option<String>,
+  // the creation of the run, jobs are only listed by run
+  #[table(time, filter(gt_eq, lt_eq))]
+  created_at: DateTime<Utc>,
+  started_at: Option<DateTime<Utc>>,
+  completed_at: Option<DateTime<Utc>>,
+  duration_seconds: Option<i64>,
+  runner_name: Option<String>,
+  actor: Option<String>,
+  url: String,
+  // these fields are passed to the github api as filters
+  #[table(filter)]
+  workflow: Option<String>,
+  #[table(required)]
+  owner: String,
+  #[table(required)]
+  repo: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct GitHubRuns {
+  total_count: u32,
+  workflow_runs: Vec<GitHubRun>,
+}
+
+#[derive(Deserialize, Debug)]
+struct GitHubRun {
+  id: u64,
+  name: String,
+  run_number: u64,
+  #[serde(default)]
+  run_attempt: Option<u32>,
+  workflow_id: u64,
+  event: String,
+  head_branch: Option<String>,
+  head_sha: String,
+  status: String,
+  conclusion: Option<String>,
+  created_at: DateTime<Utc>,
+  updated_at: DateTime<Utc>,
+  #[serde(default)]
+  run_started_at: Option<DateTime<Utc>>,
+  actor: Option<Account>,
+  html_url: String,
+  // the file of the workflow, e.g. `.github/workflows/ci.yml`
+  #[serde(default)]
+  path: Option<String>,
+}
+
+impl GitHubRun {
+  /// Runs have no completion time, but aren't updated once they completed.
+  fn completed_at(&self) -> Option<DateTime<Utc>> {
+    match self.status.as_str() {
+      "completed" => Some(self.updated_at),
+      _ => None,
+    }
+  }
+}
+
+#[derive(Deserialize, Debug)]
+struct GitHubJobs {
+  total_count: u32,
+  jobs: Vec<GitHubJob>,
+}
+
+#[derive(Deserialize, Debug)]
+struct GitHubJob {
+  id: u64,
+  #[serde(default)]
+  run_attempt: Option<u32>,
+  name: String,
+  status: String,
+  conclusion: Option<String>,
+  started_at: Option<DateTime<Utc>>,
+  completed_at: Option<DateTime<Utc>>,
+  runner_name: Option<String>,
+  html_url: String,
+}
+
+#[derive(Serialize)]
+struct RunParams<'a> {
+  #[serde(skip_serializing_if = "Option::is_none")]
+  branch: Option<&'a str>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  status: Option<&'a str>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  created: Option<String>,
+  per_page: u32,
+  page: u32,
+}
+
+#[derive(Serialize)]
+struct JobParams {
+  per_page: u32,
+  page: u32,
+}
+
+/// The filters of a query that the runs api applies.
+#[derive(Debug, Default)]
+struct RunFilters {
+  owner: String,
+  repo: String,
+  workflow: Option<String>,
+  branch: Option<String>,
+  status: Option<String>,
+  conclusion: Option<String>,
+  after: Option<DateTime<Utc>>,
+  before: Option<DateTime<Utc>>,
+  // the dashboard time range, which is applied to the rows again
+  time_range: Option<TimeRange>,
+}
+
+impl RunFilters {
+  /// The filters in `options`, leaving out the status filters of job queries, which apply to
+  /// jobs rather than their runs.
+  fn new(
+    options: &HashMap<String, String>,
+    time_range: Option<TimeRange>,
+    statuses: bool,
+  ) -> Result<Self> {
+    let filter = |op: FilterOperator| match options.get(&op.option_key("created_at")) {
+      Some(value) => parse_time(value).map(Some),
+      None => Ok(None),
+    };
+
+    Ok(Self {
+      owner: options.get("owner").unwrap_or(&"".to_owned()).clone(),
+      repo: options.get("repo").unwrap_or(&"".to_owned()).clone(),
+      workflow: options.get("workflow").cloned(),
+      branch: options.get("branch").cloned(),
+      status: options.get("status").filter(|_| statuses).cloned(),
+      conclusion: options.get("conclusion").filter(|_| statuses).cloned(),
+      after: filter(FilterOperator::GtEq)?,
+      before: filter(FilterOperator::LtEq)?,
+      time_range,
+    })
+  }
+
+  /// The api filters runs by status and conclusion with the same parameter, the other one
+  /// is applied to the fetched runs.
+  fn status_param(&self) -> Option<&str> {
+    match (&self.status, &self.conclusion) {
+      (_, Some(conclusion)) => Some(conclusion),
+      (Some(status), None) => Some(status),
+      (None, None) => None,
+    }
+  }
+
+  /// The `created` search qualifier of the filters and the time range.
+  fn created_param(&self) -> Option<String> {
+    let since = self.after.max(self.time_range.map(|r| r.from));
+    let until = match (self.before, self.time_range.map(|r| r.to)) {
+      (Some(before), Some(to)) => Some(before.min(to)),
+      (before, to) => before.or(to),
+    };
+
+    time_qualifier(since, until)
+  }
+
+  /// Whether a listed run matches the filters exactly, as the api only filters by the
+  /// second and by either the status or the conclusion.
+  fn matches(&self, run: &GitHubRun) -> bool {
+    self.status.as_ref().map_or(true, |s| s == &run.status)
+      && (self.branch.is_none() || self.branch == run.head_branch)
+      && (self.conclusion.is_none() || self.conclusion == run.conclusion)
+      && self.after.map_or(true, |after| run.created_at >= after)
+      && self.before.map_or(true, |before| run.created_at <= before)
+  }
+
+  /// A page of the runs that match the filters, and the number of pages.
+  async fn fetch_runs(&self, github: &GitHub, page: u32) -> Result<(Vec<GitHubRun>, u32)> {
+    // the api rejects unknown statuses, which match no runs
+    let known = |value: &Option<String>, values: &[&str]| {
+      value
+        .as_ref()
+        .map_or(true, |v| values.contains(&v.as_str()))
+    };
+    if !known(&self.status, STATUSES) || !known(&self.conclusion, CONCLUSIONS) {
+      return Ok((vec![], 0));
+    }
+
+    let route = match &self.workflow {
+      Some(workflow) => format!(
+        "/repos/{}/{}/actions/workflows/{}/runs",
+        self.owner, self.repo, workflow
+      ),
+      None => format!("/repos/{}/{}/actions/runs", self.owner, self.repo),
+    };
+    let params = RunParams {
+      branch: self.branch.as_deref(),
+      status: self.status_param(),
+      created: self.created_param(),
+      per_page: PER_PAGE,
+      page,
+    };
+
+    let runs: GitHubRuns = github.call(|| github.get(&route, Some(&params))).await?;
+    // pages past the limit are empty, which would silently leave runs out
+    let filtered = params.branch.is_some() || params.status.is_some() || params.created.is_some();
+    if filtered && runs.total_count > MAX_FILTERED_RUNS {
+      return Err(anyhow!(
+        "{} workflow runs match, github lists at most {} filtered runs, narrow the time range",
+        runs.total_count,
+        MAX_FILTERED_RUNS
+      ));
+    }
+    let pages = (runs.total_count + PER_PAGE - 1) / PER_PAGE;
+    let runs = runs
+      .workflow_runs
+      .into_iter()
+      .filter(|r| self.matches(r))
+      .collect();
+
+    Ok((runs, pages))
+  }
+
+  /// A run that matches the filters, by its id.
+  async fn fetch_run(&self, github: &GitHub, id: u64) -> Result<Option<GitHubRun>> {
+    let route = format!("/repos/{}/{}/actions/runs/{}", self.owner, self.repo, id);
+    let run: GitHubRun = github.call(|| github.get(&route, None::<&()>)).await?;
+
+    // workflows are filtered by their id or the name of their file
+    let workflow = self.workflow.as_ref().map_or(true, |w| {
+      *w == run.workflow_id.to_string()
+        || run
+          .path
+          .as_ref()
+          .map_or(false, |p| p.rsplit('/').next() == Some(w.as_str()))
+    });
+
+    match workflow && self.matches(&run) {
+      true => Ok(Some(run)),
+      false => Ok(None),
+    }
+  }
+}
+
+/// The next page of a list that reports its total count.
+fn next_run_page(page: u32, pages: u32) -> (Option<PageToken>, Option<u32>) {
+  match page < pages {
+    true => (Some(PageToken::Number(page + 1)), Some(pages)),
+    false => (None, Some(pages)),
+  }
+}
+
+fn duration_seconds(start: Option<DateTime<Utc>>, end: Option<DateTime<Utc>>) -> Option<i64> {
+  match (start, end) {
+    (Some(start), Some(end)) => Some((end - start).num_seconds()),
+    _ => None,
+  }
+}
+
+/// Every job of the latest attempt of a run.
+async fn fetch_jobs(
+  github: &GitHub,
+  filters: &RunFilters,
+  run: &GitHubRun,
+) -> Result<Vec<GitHubJob>> {
+  let route = format!(
+    "/repos/{}/{}/actions/runs/{}/jobs",
+    filters.owner, filters.repo, run.id
+  );
+  let mut jobs = vec![];
+
+  for page in 1.. {
+    let params = JobParams {
+      per_page: PER_PAGE,
+      page,
+    };
+    let response: GitHubJobs = github.call(|| github.get(&route, Some(&params))).await?;
+    jobs.extend(response.jobs);
+
+    if page * PER_PAGE >= response.total_count {
+      break;
+    }
+  }
+
+  Ok(jobs)
+}
+
+#[derive(Debug, Clone)]
+pub struct WorkflowRunTable {
+  clients: Clients,
+}
+
+impl WorkflowRunTable {
+  pub fn new(clients: Clients) -> Self {
+    Self { clients }
+  }
+}
+
+#[async_trait]
+impl PagedDataSource for WorkflowRunTable {
+  type Row = WorkflowRun;
+
+  fn schema(&self) -> SchemaRef {
+    WorkflowRun::table_schema()
+  }
+
+  fn capabilities(&self) -> TableCapabilities {
+    WorkflowRun::capabilities()
+  }
+
+  async fn fetch_page(
+    &self,
+    options: &HashMap<String, String>,
+    time_range: Option<TimeRange>,
+    page: PageToken,
+  ) -> Result<Page<Self::Row>> {
+    let filters = RunFilters::new(options, time_range, true)?;
+    let page = page_number(&page)?;
+    let github = self.clients.current().await?;
+    let (runs, pages) = filters.fetch_runs(&github, page).await?;
+    let (next, total_pages) = next_run_page(page, pages);
+
+    let rows = runs
+      .into_iter()
+      .map(|r| WorkflowRun {
+        id: r.id,
+        run_number: r.run_number,
+        run_attempt: r.run_attempt,
+        workflow_id: r.workflow_id,
+        event: r.event.clone(),
+        head_sha: r.head_sha.clone(),
+        started_at: r.run_started_at,
+        completed_at: r.completed_at(),
+        duration_seconds: duration_seconds(r.run_started_at, r.completed_at()),
+        actor: r.actor.as_ref().map(|a| a.login.clone()),
+        url: r.html_url.clone(),
+        created_at: r.created_at,
+        workflow_name: r.name,
+        branch: r.head_branch,
+        status: r.status,
+        conclusion: r.conclusion,
+        workflow: filters.workflow.clone(),
+        owner: filters.owner.clone(),
+        repo: filters.repo.clone(),
+      })
+      .collect();
+
+    Ok(Page {
+      rows,
+      next,
+      total_pages,
+    })
+  }
+}
+
+/// Lists the jobs of the runs that match the filters, a page of runs at a time.
+#[derive(Debug, Clone)]
+pub struct WorkflowJobTable {
+  clients: Clients,
+}
+
+impl WorkflowJobTable {
+  pub fn new(clients: Clients) -> Self {
+    Self { clients }
+  }
+}
+
+#[async_trait]
+impl PagedDataSource for WorkflowJobTable {
+  type Row = WorkflowJob;
+
+  fn schema(&self) -> SchemaRef {
+    WorkflowJob::table_schema()
+  }
+
+  fn capabilities(&self) -> TableCapabilities {
+    WorkflowJob::capabilities()
+  }
+
+  async fn fetch_page(
+    &self,
+    options: &HashMap<String, String>,
+    time_range: Option<TimeRange>,
+    page: PageToken,
+  ) -> Result<Page<Self::Row>> {
+    let filters = RunFilters::new(options, time_range, false)?;
+    let page = page_number(&page)?;
+    let github = self.clients.current().await?;
+    let run_id: Option<u64> = match options.get("run_id") {
+      Some(id) => Some(id.parse()?),
+      None => None,
+    };
+
+    let (runs, (next, total_pages)) = match run_id {
+      Some(id) => {
+        let runs = filters.fetch_run(&github, id).await?.into_iter().collect();
+        (runs, (None, None))
+      }
+      None => {
+        let (runs, pages) = filters.fetch_runs(&github, page).await?;
+        (runs, next_run_page(page, pages))
+      }
+    };
+
+    let jobs: Vec<Vec<GitHubJob>> = stream::iter(runs.iter())
+      .map(|r| fetch_jobs(&github, &filters, r))
+      .buffered(JOBS_CONCURRENCY)
+      .try_collect()
+      .await?;
+
+    let rows = runs
+      .iter()
+      .zip(jobs)
+      .flat_map(|(r, jobs)| {
+        let filters = &filters;
+        jobs.into_iter().map(move |j| WorkflowJob {
+          id: j.id,
+          run_id: r.id,
+          run_attempt: j.run_attempt.or(r.run_attempt),
+          name: j.name,
+          workflow_name: r.name.clone(),
+          event: r.event.clone(),
+          branch: r.head_branch.clone(),
+          status: j.status,
+          conclusion: j.conclusion,
+          created_at: r.created_at,
+          started_at: j.started_at,
+          completed_at: j.completed_at,
+          duration_seconds: duration_seconds(j.started_at, j.completed_at),
+          runner_name: j.runner_name,
+          actor: r.actor.as_ref().map(|a| a.login.clone()),
+          url: j.html_url,
+          workflow: filters.workflow.clone(),
+          owner: filters.owner.clone(),
+          repo: filters.repo.clone(),
+        })
+      })
+      .collect();
+
+    Ok(Page {
+      rows,
+      next,
+      total_pages,
+    })
+  }
+}
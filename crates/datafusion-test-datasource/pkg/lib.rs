@@ -0,0 +1,87 @@
+pub mod github;
+
+use std::env;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use github::{
+  Clients, CommitTable, IssueTable, PullRequestReviewTable, PullRequestTable, ReleaseTable, Repos,
+  RepositoryTable, WorkflowJobTable, WorkflowRunTable,
+};
+use grafana_plugin_sdk::{
+  CacheConfig, CachedDataSource, DiskCacheConfig, FanOut, JSONTableProvider, Pages, Plugin,
+  StreamingDataSource,
+};
+
+/// Registers a github table behind a cache of its own.
+///
+/// Tables that fetch every row of a repo ignore the time range, so that one cache entry serves
+/// every dashboard. Their rows are filtered on their time column afterwards.
+async fn register_table<D: StreamingDataSource>(
+  plugin: &Plugin,
+  name: &str,
+  datasource: D,
+  ignore_time_range: bool,
+) {
+  // results are also kept on disk if a cache directory is configured
+  let disk = env::var("GF_PLUGIN_CACHE_DIR")
+    .ok()
+    .map(|d| DiskCacheConfig::new(PathBuf::from(d).join(name)));
+
+  let cache = CacheConfig {
+    ignore_time_range,
+    disk,
+    ..Default::default()
+  };
+
+  let datasource = CachedDataSource::new(datasource, cache);
+  plugin
+    .register_reporter(name, Arc::new(datasource.clone()))
+    .expect("failed to register cache reporter");
+
+  plugin
+    .register_table(
+      name.to_owned(),
+      Arc::new(JSONTableProvider::new(datasource)),
+    )
+    .await
+    .unwrap_or_else(|e| panic!("failed to register {}: {}", name, e));
+}
+
+/// Registers every github table, the rate limit reporter and the health check of `clients`.
+///
+/// The tables use the credentials and the rate limit of the datasource instance they are
+/// queried through.
+pub async fn register(plugin: &Plugin, clients: Clients) {
+  plugin
+    .register_reporter("github", Arc::new(clients.clone()))
+    .expect("failed to register rate limit reporter");
+  plugin
+    .register_health_check(Arc::new(clients.clone()))
+    .expect("failed to register github health check");
+
+  let repositories = Pages::new(RepositoryTable::new(clients.clone()));
+  register_table(plugin, "repositories", repositories, true).await;
+  // these tables read every repo of the owner when a query has no repo filter
+  let repos = Repos::new(clients.clone());
+  let releases = Pages::new(ReleaseTable::new(clients.clone()));
+  let releases = FanOut::new(releases, repos.clone());
+  register_table(plugin, "releases", releases, true).await;
+  let pull_requests = Pages::new(PullRequestTable::new(clients.clone()));
+  let pull_requests = FanOut::new(pull_requests, repos.clone());
+  register_table(plugin, "pull_requests", pull_requests, true).await;
+  let issues = Pages::new(IssueTable::new(clients.clone()));
+  let issues = FanOut::new(issues, repos);
+  register_table(plugin, "issues", issues, true).await;
+  // the history of a repo is too long to fetch all of it
+  let commits = Pages::new(CommitTable::new(clients.clone()));
+  register_table(plugin, "commits", commits, false).await;
+  // as are the runs of its workflows
+  let workflow_runs = Pages::new(WorkflowRunTable::new(clients.clone()));
+  register_table(plugin, "workflow_runs", workflow_runs, false).await;
+  let workflow_jobs = Pages::new(WorkflowJobTable::new(clients.clone()));
+  register_table(plugin, "workflow_jobs", workflow_jobs, false).await;
+  // reviews are fetched for the pull requests updated in the time range
+  let reviews = Pages::new(PullRequestReviewTable::new(clients));
+  register_table(plugin, "pull_request_reviews", reviews, false).await;
+}
@@ -1,25 +1,10 @@
-mod github;
-
-use std::sync::Arc;
-
-use github::{PullRequestTable, ReleaseTable};
-use grafana_plugin_sdk::{start, JSONTableProvider, Plugin};
+use datafusion_test_datasource::github::Clients;
+use grafana_plugin_sdk::{start, Plugin, RetryPolicy};
 
 #[tokio::main]
 async fn main() {
   let plugin = Plugin::new("github");
-
-  let releases = JSONTableProvider::new(ReleaseTable);
-  plugin
-    .register_table("releases".to_owned(), Arc::new(releases))
-    .await
-    .expect("failed to register releases");
-
-  let prs = JSONTableProvider::new(PullRequestTable);
-  plugin
-    .register_table("pull_requests".to_owned(), Arc::new(prs))
-    .await
-    .expect("failed to register pull_requests");
+  datafusion_test_datasource::register(&plugin, Clients::new(RetryPolicy::default())).await;
 
   start(plugin).await.unwrap();
 }
@@ -0,0 +1,122 @@
+import React, { ChangeEvent } from 'react';
+import { DataSourcePluginOptionsEditorProps, SelectableValue } from '@grafana/data';
+import { Button, InlineField, InlineSwitch, Input, LegacyForms, RadioButtonGroup, TextArea } from '@grafana/ui';
+import { DatafusionOptions, DatafusionSecureOptions, GitHubApi } from 'types';
+
+const { SecretFormField } = LegacyForms;
+
+const apiOptions: Array<SelectableValue<GitHubApi>> = [
+  { label: 'REST', value: 'rest' },
+  { label: 'GraphQL', value: 'graphql' },
+];
+
+export type Props = DataSourcePluginOptionsEditorProps<DatafusionOptions, DatafusionSecureOptions>;
+
+export const ConfigEditor = (props: Props) => {
+  const { onOptionsChange, options } = props;
+  const { jsonData, secureJsonFields } = options;
+  const secureJsonData = options.secureJsonData || {};
+
+  const handleUrlChange = (e: ChangeEvent<HTMLInputElement>) => {
+    onOptionsChange({ ...options, url: e.currentTarget.value });
+  };
+
+  const handleJsonDataChange = (key: keyof DatafusionOptions) => (e: ChangeEvent<HTMLInputElement>) => {
+    onOptionsChange({ ...options, jsonData: { ...jsonData, [key]: e.currentTarget.value } });
+  };
+
+  const handleApiChange = (api?: GitHubApi) => {
+    onOptionsChange({ ...options, jsonData: { ...jsonData, api } });
+  };
+
+  const handleTextSearchChange = (e: React.FormEvent<HTMLInputElement>) => {
+    onOptionsChange({ ...options, jsonData: { ...jsonData, textSearch: e.currentTarget.checked } });
+  };
+
+  const handleSecretChange = (key: keyof DatafusionSecureOptions) => (
+    e: ChangeEvent<HTMLInputElement | HTMLTextAreaElement>
+  ) => {
+    onOptionsChange({ ...options, secureJsonData: { ...secureJsonData, [key]: e.currentTarget.value } });
+  };
+
+  const handleSecretReset = (key: keyof DatafusionSecureOptions) => () => {
+    onOptionsChange({
+      ...options,
+      secureJsonFields: { ...secureJsonFields, [key]: false },
+      secureJsonData: { ...secureJsonData, [key]: '' },
+    });
+  };
+
+  return (
+    <>
+      <h3 className="page-heading">GitHub</h3>
+      <InlineField
+        label="URL"
+        labelWidth={20}
+        tooltip="The URL of a GitHub Enterprise Server. Leave empty for github.com"
+      >
+        <Input width={30} placeholder="https://api.github.com" value={options.url || ''} onChange={handleUrlChange} />
+      </InlineField>
+      <InlineField
+        label="CA certificate"
+        labelWidth={20}
+        tooltip="The PEM encoded certificate of the private CA that signed the certificate of the server, if any"
+      >
+        {secureJsonFields?.tlsCACert ? (
+          <>
+            <Input width={30} disabled value="configured" />
+            <Button variant="secondary" onClick={handleSecretReset('tlsCACert')}>
+              Reset
+            </Button>
+          </>
+        ) : (
+          <TextArea cols={60} rows={6} value={secureJsonData.tlsCACert || ''} onChange={handleSecretChange('tlsCACert')} />
+        )}
+      </InlineField>
+      <InlineField
+        label="API"
+        labelWidth={20}
+        tooltip="GraphQL fetches only the columns a query reads, and the reviews of pull requests with them. It needs credentials"
+      >
+        <RadioButtonGroup options={apiOptions} value={jsonData.api || 'rest'} onChange={handleApiChange} />
+      </InlineField>
+      <InlineField
+        label="Text search"
+        labelWidth={20}
+        tooltip="Apply LIKE filters on issues and pull requests with the GitHub search API. It matches whole words only, so rows that only contain a pattern inside a word are left out"
+      >
+        <InlineSwitch value={jsonData.textSearch || false} onChange={handleTextSearchChange} />
+      </InlineField>
+      <p>Authenticate with a personal access token, or as an installation of a GitHub App.</p>
+      <SecretFormField
+        label="Access token"
+        labelWidth={10}
+        inputWidth={20}
+        placeholder="ghp_..."
+        value={secureJsonData.accessToken || ''}
+        isConfigured={secureJsonFields?.accessToken}
+        onChange={handleSecretChange('accessToken')}
+        onReset={handleSecretReset('accessToken')}
+      />
+      <h3 className="page-heading">GitHub App</h3>
+      <InlineField label="App ID" labelWidth={20}>
+        <Input width={30} value={jsonData.appId || ''} onChange={handleJsonDataChange('appId')} />
+      </InlineField>
+      <InlineField label="Installation ID" labelWidth={20}>
+        <Input width={30} value={jsonData.installationId || ''} onChange={handleJsonDataChange('installationId')} />
+      </InlineField>
+      <InlineField label="Private key" labelWidth={20} tooltip="The PEM encoded private key of the app">
+        {secureJsonFields?.privateKey ? (
+          <>
+            <Input width={30} disabled value="configured" />
+            <Button variant="secondary" onClick={handleSecretReset('privateKey')}>
+              Reset
+            </Button>
+          </>
+        ) : (
+          <TextArea cols={60} rows={6} value={secureJsonData.privateKey || ''} onChange={handleSecretChange('privateKey')} />
+        )}
+      </InlineField>
+    </>
+  );
+};
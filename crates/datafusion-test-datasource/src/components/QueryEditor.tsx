@@ -1,13 +1,14 @@
 import React from 'react';
 import { QueryEditorProps } from '@grafana/data';
-import { CodeEditor, InfoBox } from '@grafana/ui';
+import { Alert, CodeEditor, InfoBox, InlineField, InlineSwitch } from '@grafana/ui';
 import { DatafusionDatasource } from 'datasource';
 import { DatafusionQuery } from 'types';
 
 export type Props = QueryEditorProps<DatafusionDatasource, DatafusionQuery>;
 
 export const QueryEditor = (props: Props) => {
-  const { onChange, onRunQuery, query } = props;
+  const { onChange, onRunQuery, query, data } = props;
+  const error = data?.error && (!data.error.refId || data.error.refId === query.refId) ? data.error : undefined;
 
   const handleChange = (q: string) => {
     if (q.trim()) {
@@ -16,6 +17,16 @@ export const QueryEditor = (props: Props) => {
     }
   };
 
+  const handleIgnoreTimeRangeChange = (e: React.FormEvent<HTMLInputElement>) => {
+    onChange({ ...query, ignoreTimeRange: e.currentTarget.checked });
+    onRunQuery();
+  };
+
+  const handleSkipInvalidRowsChange = (e: React.FormEvent<HTMLInputElement>) => {
+    onChange({ ...query, skipInvalidRows: e.currentTarget.checked });
+    onRunQuery();
+  };
+
   return (
     <>
       <CodeEditor
@@ -28,6 +39,17 @@ export const QueryEditor = (props: Props) => {
         onBlur={handleChange}
         onSave={handleChange}
       />
+      <InlineField label="Ignore time range" tooltip="Query all rows instead of the dashboard time range">
+        <InlineSwitch value={query.ignoreTimeRange || false} onChange={handleIgnoreTimeRangeChange} />
+      </InlineField>
+      <InlineField label="Skip invalid rows" tooltip="Drop rows that don't match the table schema instead of failing the query">
+        <InlineSwitch value={query.skipInvalidRows || false} onChange={handleSkipInvalidRowsChange} />
+      </InlineField>
+      {error?.message && (
+        <Alert severity="error" title="Query error">
+          {error.message}
+        </Alert>
+      )}
       <InfoBox>
         To save and re-run the query, press <code>ctrl + s</code> / <code>cmd + s</code>.
       </InfoBox>
@@ -1,9 +1,9 @@
 import { DataSourceInstanceSettings, DataQueryRequest, DataQueryResponse, ScopedVars } from '@grafana/data';
 import { DataSourceWithBackend, getTemplateSrv } from '@grafana/runtime';
 import { Observable } from 'rxjs';
-import { DatafusionQuery } from 'types';
-export class DatafusionDatasource extends DataSourceWithBackend<DatafusionQuery> {
-  constructor(instanceSettings: DataSourceInstanceSettings) {
+import { DatafusionOptions, DatafusionQuery } from 'types';
+export class DatafusionDatasource extends DataSourceWithBackend<DatafusionQuery, DatafusionOptions> {
+  constructor(instanceSettings: DataSourceInstanceSettings<DatafusionOptions>) {
     super(instanceSettings);
   }
 
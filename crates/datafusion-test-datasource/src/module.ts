@@ -1,7 +1,11 @@
 import { DataSourcePlugin } from '@grafana/data';
+import { ConfigEditor } from 'components/ConfigEditor';
 import { QueryEditor } from 'components/QueryEditor';
 import { DatafusionDatasource } from './datasource';
+import { DatafusionOptions, DatafusionQuery } from './types';
 
-export const plugin = new DataSourcePlugin<DatafusionDatasource>(DatafusionDatasource)
+export const plugin = new DataSourcePlugin<DatafusionDatasource, DatafusionQuery, DatafusionOptions>(
+  DatafusionDatasource
+)
   .setQueryEditor(QueryEditor)
-  .setConfigEditor(() => null);
+  .setConfigEditor(ConfigEditor);
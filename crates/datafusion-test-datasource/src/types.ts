@@ -1,5 +1,22 @@
-import { DataQuery } from '@grafana/data';
+import { DataQuery, DataSourceJsonData } from '@grafana/data';
 
 export interface DatafusionQuery extends DataQuery {
   sql: string;
+  ignoreTimeRange?: boolean;
+  skipInvalidRows?: boolean;
+}
+
+export type GitHubApi = 'rest' | 'graphql';
+
+export interface DatafusionOptions extends DataSourceJsonData {
+  appId?: string;
+  installationId?: string;
+  api?: GitHubApi;
+  textSearch?: boolean;
+}
+
+export interface DatafusionSecureOptions {
+  accessToken?: string;
+  privateKey?: string;
+  tlsCACert?: string;
 }
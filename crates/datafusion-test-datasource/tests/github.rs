@@ -0,0 +1,527 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+use arrow::array::{
+  Array, BooleanArray, Int64Array, ListArray, StringArray, TimestampNanosecondArray, UInt64Array,
+};
+use arrow::record_batch::RecordBatch;
+use chrono::{TimeZone, Utc};
+use datafusion_test_datasource::github::Clients;
+use grafana_plugin_sdk::proto::pluginv2::{DataSourceInstanceSettings, PluginContext};
+use grafana_plugin_sdk::{DataProvider, Plugin, Query, Reporter, RetryPolicy};
+use serde_json::json;
+use wiremock::matchers::{header, method, path, path_regex, query_param};
+use wiremock::{Mock, MockServer, Request, ResponseTemplate};
+
+const REPO: &str = include_str!("fixtures/repo.json");
+const RELEASES_PAGE_1: &str = include_str!("fixtures/releases_page_1.json");
+const RELEASES_PAGE_2: &str = include_str!("fixtures/releases_page_2.json");
+const ISSUES: &str = include_str!("fixtures/issues.json");
+const SEARCH_ISSUES: &str = include_str!("fixtures/search_issues.json");
+const GRAPHQL_PULL_REQUESTS: &str = include_str!("fixtures/graphql_pull_requests.json");
+// a self-signed CA, which the mock server doesn't need as it serves plain http
+const CA_CERTIFICATE: &str = include_str!("fixtures/ca.pem");
+
+/// Matches a route of the rest api of a server, which is served under `/api/v3`.
+///
+/// octocrab resolves routes that start with a slash against the host, so requests may leave the
+/// path of the api url out.
+fn api(route: &str) -> wiremock::matchers::PathRegexMatcher {
+  path_regex(format!("^(/api/v3)?{}$", route))
+}
+
+fn json(body: &str) -> ResponseTemplate {
+  ResponseTemplate::new(200).set_body_raw(body, "application/json")
+}
+
+fn github_error(status: u16, message: &str) -> ResponseTemplate {
+  let body = json!({
+    "message": message,
+    "documentation_url": "https://docs.github.com/rest",
+  });
+  ResponseTemplate::new(status).set_body_json(body)
+}
+
+/// A mock github server with the grafana/tempo repo, and a plugin with every table that
+/// queries it.
+async fn setup() -> (MockServer, Plugin, Clients) {
+  let server = MockServer::start().await;
+  Mock::given(method("GET"))
+    .and(api("/repos/grafana/tempo"))
+    .respond_with(json(REPO))
+    .mount(&server)
+    .await;
+
+  let plugin = Plugin::new("github");
+  let clients = Clients::new(RetryPolicy::default());
+  datafusion_test_datasource::register(&plugin, clients.clone()).await;
+  (server, plugin, clients)
+}
+
+/// A datasource instance with an access token, whose url is the host of the mock server.
+fn context(server: &MockServer, json_data: &str) -> PluginContext {
+  let mut secrets = HashMap::new();
+  secrets.insert("accessToken".to_owned(), "ghp_test".to_owned());
+
+  PluginContext {
+    org_id: 1,
+    data_source_instance_settings: Some(DataSourceInstanceSettings {
+      id: 1,
+      url: server.uri(),
+      json_data: json_data.as_bytes().to_vec(),
+      decrypted_secure_json_data: secrets,
+      ..Default::default()
+    }),
+    ..Default::default()
+  }
+}
+
+/// The context with the PEM encoded certificate of a private CA.
+fn with_ca_certificate(mut context: PluginContext, pem: &str) -> PluginContext {
+  if let Some(instance) = context.data_source_instance_settings.as_mut() {
+    instance
+      .decrypted_secure_json_data
+      .insert("tlsCACert".to_owned(), pem.to_owned());
+  }
+  context
+}
+
+async fn query(plugin: &Plugin, context: PluginContext, sql: &str) -> Result<Vec<RecordBatch>> {
+  plugin
+    .handle_query(Query {
+      ref_id: "A".to_owned(),
+      query_type: "".to_owned(),
+      interval_ms: 0,
+      time_range: Default::default(),
+      max_data_points: 0,
+      sql: sql.to_owned(),
+      ignore_time_range: true,
+      skip_invalid_rows: false,
+      plugin_context: Some(context),
+    })
+    .await
+}
+
+/// The values of a column of every batch, read from its arrays with `read`.
+fn values<A: Array + 'static, T>(
+  batches: &[RecordBatch],
+  column: &str,
+  read: impl Fn(&A, usize) -> T,
+) -> Vec<T> {
+  batches
+    .iter()
+    .flat_map(|batch| {
+      let index = batch.schema().index_of(column).unwrap();
+      let array = batch
+        .column(index)
+        .as_any()
+        .downcast_ref::<A>()
+        .unwrap_or_else(|| panic!("unexpected type of {}", column));
+      (0..array.len()).map(|i| read(array, i)).collect::<Vec<T>>()
+    })
+    .collect()
+}
+
+fn strings(batches: &[RecordBatch], column: &str) -> Vec<String> {
+  values(batches, column, |a: &StringArray, i| a.value(i).to_owned())
+}
+
+fn lists(batches: &[RecordBatch], column: &str) -> Vec<Vec<String>> {
+  values(batches, column, |a: &ListArray, i| {
+    let list = a.value(i);
+    let list = list.as_any().downcast_ref::<StringArray>().unwrap();
+    (0..list.len())
+      .map(|j| list.value(j).to_owned())
+      .collect::<Vec<String>>()
+  })
+}
+
+#[tokio::test]
+async fn releases_are_paged_with_link_headers() {
+  let (server, plugin, _) = setup().await;
+  let next = format!(
+    "{}/api/v3/repositories/283015215/releases?per_page=100&page=2",
+    server.uri()
+  );
+  let link = format!("<{}>; rel=\"next\", <{}>; rel=\"last\"", next, next);
+  Mock::given(method("GET"))
+    .and(api("/repos/grafana/tempo/releases"))
+    .and(query_param("page", "1"))
+    .respond_with(json(RELEASES_PAGE_1).insert_header("Link", link.as_str()))
+    .expect(1)
+    .mount(&server)
+    .await;
+  Mock::given(method("GET"))
+    .and(api("/repos/grafana/tempo/releases"))
+    .and(query_param("page", "2"))
+    .respond_with(json(RELEASES_PAGE_2))
+    .expect(1)
+    .mount(&server)
+    .await;
+
+  let sql = "SELECT tag_name, pre_release FROM datasource.github.releases \
+    WHERE owner = 'grafana' AND repo = 'tempo' ORDER BY created_at DESC";
+  let batches = query(&plugin, context(&server, ""), sql).await.unwrap();
+
+  assert_eq!(
+    strings(&batches, "tag_name"),
+    vec!["v1.0.1", "v1.0.0", "v1.0.0-rc.0"]
+  );
+}
+
+#[tokio::test]
+async fn timestamps_are_converted_to_nanoseconds() {
+  let (server, plugin, _) = setup().await;
+  Mock::given(method("GET"))
+    .and(api("/repos/grafana/tempo/releases"))
+    .respond_with(json(RELEASES_PAGE_2))
+    .mount(&server)
+    .await;
+
+  let sql = "SELECT published_at FROM datasource.github.releases \
+    WHERE owner = 'grafana' AND repo = 'tempo'";
+  let batches = query(&plugin, context(&server, ""), sql).await.unwrap();
+
+  let published_at = Utc.ymd(2021, 5, 27).and_hms(18, 31, 0).timestamp_nanos();
+  assert_eq!(
+    values(
+      &batches,
+      "published_at",
+      |a: &TimestampNanosecondArray, i| a.value(i)
+    ),
+    vec![published_at]
+  );
+}
+
+#[tokio::test]
+async fn empty_repos_have_no_rows() {
+  let (server, plugin, _) = setup().await;
+  Mock::given(method("GET"))
+    .and(api("/repos/grafana/tempo/releases"))
+    .respond_with(json("[]"))
+    .mount(&server)
+    .await;
+
+  let sql = "SELECT tag_name FROM datasource.github.releases \
+    WHERE owner = 'grafana' AND repo = 'tempo'";
+  let batches = query(&plugin, context(&server, ""), sql).await.unwrap();
+
+  assert_eq!(batches.iter().map(|b| b.num_rows()).sum::<usize>(), 0);
+}
+
+#[tokio::test]
+async fn filters_are_pushed_down_to_the_api() {
+  let (server, plugin, _) = setup().await;
+  // unmatched requests get a 404, which fails the query
+  Mock::given(method("GET"))
+    .and(api("/repos/grafana/tempo/issues"))
+    .and(query_param("state", "open"))
+    .and(query_param("labels", "bug"))
+    .and(query_param("creator", "mdisibio"))
+    .respond_with(json(ISSUES))
+    .expect(1)
+    .mount(&server)
+    .await;
+
+  let sql = "SELECT number, label, labels, assignees FROM datasource.github.issues \
+    WHERE owner = 'grafana' AND repo = 'tempo' \
+    AND state = 'open' AND label = 'bug' AND author = 'mdisibio'";
+  let batches = query(&plugin, context(&server, ""), sql).await.unwrap();
+
+  // the api matches authors regardless of case, so rows are filtered again
+  assert_eq!(
+    values(&batches, "number", |a: &Int64Array, i| a.value(i)),
+    vec![781]
+  );
+  assert_eq!(strings(&batches, "label"), vec!["bug"]);
+  assert_eq!(
+    lists(&batches, "labels"),
+    vec![vec!["bug".to_owned(), "component/compactor".to_owned()]]
+  );
+  assert_eq!(lists(&batches, "assignees"), vec![Vec::<String>::new()]);
+}
+
+#[tokio::test]
+async fn pull_requests_are_flagged_in_listed_issues() {
+  let (server, plugin, _) = setup().await;
+  // the rest api lists pull requests as issues with a pull_request field
+  let mut issues: Vec<serde_json::Value> = serde_json::from_str(ISSUES).unwrap();
+  let mut pull_request = issues[0].clone();
+  pull_request["number"] = json!(790);
+  pull_request["pull_request"] = json!({
+    "url": "https://api.github.com/repos/grafana/tempo/pulls/790",
+  });
+  issues.insert(0, pull_request);
+  Mock::given(method("GET"))
+    .and(api("/repos/grafana/tempo/issues"))
+    .respond_with(ResponseTemplate::new(200).set_body_json(issues))
+    .mount(&server)
+    .await;
+
+  let sql = "SELECT number, is_pull_request FROM datasource.github.issues \
+    WHERE owner = 'grafana' AND repo = 'tempo'";
+  let batches = query(&plugin, context(&server, ""), sql).await.unwrap();
+
+  assert_eq!(
+    values(&batches, "number", |a: &Int64Array, i| a.value(i)),
+    vec![790, 781, 774]
+  );
+  assert_eq!(
+    values(&batches, "is_pull_request", |a: &BooleanArray, i| a
+      .value(i)),
+    vec![true, false, false]
+  );
+}
+
+#[tokio::test]
+async fn created_at_ranges_are_applied_to_listed_issues() {
+  let (server, plugin, _) = setup().await;
+  Mock::given(method("GET"))
+    .and(api("/repos/grafana/tempo/issues"))
+    .respond_with(json(ISSUES))
+    .expect(1)
+    .mount(&server)
+    .await;
+  Mock::given(method("GET"))
+    .and(api("/search/issues"))
+    .respond_with(json(SEARCH_ISSUES))
+    .expect(0)
+    .mount(&server)
+    .await;
+
+  let sql = "SELECT number FROM datasource.github.issues \
+    WHERE owner = 'grafana' AND repo = 'tempo' AND created_at >= '2021-06-25T00:00:00Z'";
+  let batches = query(&plugin, context(&server, ""), sql).await.unwrap();
+
+  assert_eq!(
+    values(&batches, "number", |a: &Int64Array, i| a.value(i)),
+    vec![781]
+  );
+}
+
+#[tokio::test]
+async fn like_filters_match_inside_words_without_text_search() {
+  let (server, plugin, _) = setup().await;
+  Mock::given(method("GET"))
+    .and(api("/repos/grafana/tempo/issues"))
+    .respond_with(json(ISSUES))
+    .expect(1)
+    .mount(&server)
+    .await;
+  Mock::given(method("GET"))
+    .and(api("/search/issues"))
+    .respond_with(json(SEARCH_ISSUES))
+    .expect(0)
+    .mount(&server)
+    .await;
+
+  let sql = "SELECT number FROM datasource.github.issues \
+    WHERE owner = 'grafana' AND repo = 'tempo' AND title LIKE '%pani%'";
+  let batches = query(&plugin, context(&server, ""), sql).await.unwrap();
+
+  // search would only find whole words
+  assert_eq!(
+    values(&batches, "number", |a: &Int64Array, i| a.value(i)),
+    vec![774]
+  );
+}
+
+#[tokio::test]
+async fn like_filters_are_searched_and_applied_again() {
+  let (server, plugin, _) = setup().await;
+  Mock::given(method("GET"))
+    .and(api("/search/issues"))
+    .and(query_param("q", "repo:grafana/tempo \"panic\" in:title"))
+    .respond_with(json(SEARCH_ISSUES))
+    .expect(1)
+    .mount(&server)
+    .await;
+
+  let sql = "SELECT number FROM datasource.github.issues \
+    WHERE owner = 'grafana' AND repo = 'tempo' AND title LIKE '%panic%'";
+  let context = context(&server, r#"{"textSearch": true}"#);
+  let batches = query(&plugin, context, sql).await.unwrap();
+
+  // search also finds "PANIC", which the pattern doesn't match
+  assert_eq!(
+    values(&batches, "number", |a: &Int64Array, i| a.value(i)),
+    vec![774]
+  );
+}
+
+#[tokio::test]
+async fn searches_past_the_result_limit_fail_the_query() {
+  let (server, plugin, _) = setup().await;
+  Mock::given(method("GET"))
+    .and(api("/search/issues"))
+    .respond_with(json(
+      r#"{"total_count": 1500, "incomplete_results": false, "items": []}"#,
+    ))
+    .mount(&server)
+    .await;
+
+  let sql = "SELECT number FROM datasource.github.issues \
+    WHERE owner = 'grafana' AND repo = 'tempo' AND title LIKE '%panic%'";
+  let context = context(&server, r#"{"textSearch": true}"#);
+  let error = query(&plugin, context, sql).await.unwrap_err();
+
+  assert!(
+    format!("{:?}", error).contains("1500 results"),
+    "{:?}",
+    error
+  );
+}
+
+#[tokio::test]
+async fn rate_limits_are_retried() {
+  let (server, plugin, clients) = setup().await;
+  Mock::given(method("GET"))
+    .and(api("/repos/grafana/tempo/issues"))
+    .respond_with(github_error(403, "API rate limit exceeded for user ID 1."))
+    .up_to_n_times(1)
+    .with_priority(1)
+    .mount(&server)
+    .await;
+  Mock::given(method("GET"))
+    .and(api("/repos/grafana/tempo/issues"))
+    .respond_with(json(ISSUES))
+    .mount(&server)
+    .await;
+  // the limit has already reset, so the retry isn't delayed
+  let rate_limit = json!({
+    "resources": {},
+    "rate": { "limit": 5000, "remaining": 0, "reset": Utc::now().timestamp(), "used": 5000 },
+  });
+  Mock::given(method("GET"))
+    .and(api("/rate_limit"))
+    .respond_with(ResponseTemplate::new(200).set_body_json(rate_limit))
+    .mount(&server)
+    .await;
+
+  let sql = "SELECT number FROM datasource.github.issues \
+    WHERE owner = 'grafana' AND repo = 'tempo'";
+  let batches = query(&plugin, context(&server, ""), sql).await.unwrap();
+
+  assert_eq!(batches.iter().map(|b| b.num_rows()).sum::<usize>(), 2);
+  assert_eq!(clients.report()["rateLimited"], 1);
+}
+
+#[tokio::test]
+async fn server_errors_are_retried() {
+  let (server, plugin, clients) = setup().await;
+  Mock::given(method("GET"))
+    .and(api("/repos/grafana/tempo/issues"))
+    .respond_with(github_error(502, "Bad Gateway"))
+    .up_to_n_times(1)
+    .with_priority(1)
+    .mount(&server)
+    .await;
+  Mock::given(method("GET"))
+    .and(api("/repos/grafana/tempo/issues"))
+    .respond_with(json(ISSUES))
+    .mount(&server)
+    .await;
+
+  let sql = "SELECT number FROM datasource.github.issues \
+    WHERE owner = 'grafana' AND repo = 'tempo'";
+  let batches = query(&plugin, context(&server, ""), sql).await.unwrap();
+
+  assert_eq!(batches.iter().map(|b| b.num_rows()).sum::<usize>(), 2);
+  assert_eq!(clients.report()["retries"], 1);
+}
+
+#[tokio::test]
+async fn api_errors_fail_the_query() {
+  let (server, plugin, _) = setup().await;
+  Mock::given(method("GET"))
+    .and(api("/repos/grafana/missing"))
+    .respond_with(github_error(404, "Not Found"))
+    .mount(&server)
+    .await;
+
+  let sql = "SELECT tag_name FROM datasource.github.releases \
+    WHERE owner = 'grafana' AND repo = 'missing'";
+  let error = query(&plugin, context(&server, ""), sql).await.unwrap_err();
+
+  assert!(format!("{:?}", error).contains("Not Found"), "{:?}", error);
+}
+
+#[tokio::test]
+async fn graphql_queries_only_read_the_projected_columns() {
+  let (server, plugin, clients) = setup().await;
+  Mock::given(method("POST"))
+    .and(path("/api/graphql"))
+    .and(|request: &Request| {
+      let body = String::from_utf8_lossy(&request.body);
+      body.contains("rateLimit")
+        && body.contains("reviews(first: 100")
+        && !body.contains("additions")
+    })
+    .respond_with(json(GRAPHQL_PULL_REQUESTS))
+    .expect(1)
+    .mount(&server)
+    .await;
+
+  let sql = "SELECT number, author, reviewers FROM datasource.github.pull_requests \
+    WHERE owner = 'grafana' AND repo = 'tempo' ORDER BY number DESC";
+  let context = context(&server, r#"{"api": "graphql"}"#);
+  let batches = query(&plugin, context, sql).await.unwrap();
+
+  assert_eq!(
+    values(&batches, "number", |a: &UInt64Array, i| a.value(i)),
+    vec![779, 776]
+  );
+  assert_eq!(strings(&batches, "author"), vec!["mdisibio", "ghost"]);
+  // replies of the author are left out
+  assert_eq!(
+    lists(&batches, "reviewers"),
+    vec![
+      vec!["annanay25".to_owned(), "joe-elliott".to_owned()],
+      vec![]
+    ]
+  );
+
+  let cost = &clients.report()["graphql"];
+  assert_eq!(cost["queries"], 1);
+  assert_eq!(cost["cost"], 1);
+}
+
+#[tokio::test]
+async fn requests_are_sent_with_the_ca_certificate_of_the_instance() {
+  let (server, plugin, _) = setup().await;
+  Mock::given(method("GET"))
+    .and(api("/repos/grafana/tempo/releases"))
+    .and(query_param("page", "1"))
+    .and(header("authorization", "Bearer ghp_test"))
+    .respond_with(json(RELEASES_PAGE_2))
+    .expect(1)
+    .mount(&server)
+    .await;
+
+  let sql = "SELECT tag_name FROM datasource.github.releases \
+    WHERE owner = 'grafana' AND repo = 'tempo'";
+  let context = with_ca_certificate(context(&server, ""), CA_CERTIFICATE);
+  let batches = query(&plugin, context, sql).await.unwrap();
+
+  assert_eq!(strings(&batches, "tag_name"), vec!["v1.0.0-rc.0"]);
+}
+
+#[tokio::test]
+async fn invalid_ca_certificates_fail_the_query() {
+  let (server, plugin, _) = setup().await;
+  let sql = "SELECT tag_name FROM datasource.github.releases \
+    WHERE owner = 'grafana' AND repo = 'tempo'";
+
+  for pem in &[
+    "not a certificate",
+    "-----BEGIN CERTIFICATE-----\nbm90IGEgY2VydGlmaWNhdGU=\n-----END CERTIFICATE-----",
+  ] {
+    let context = with_ca_certificate(context(&server, ""), pem);
+    let error = query(&plugin, context, sql).await.unwrap_err();
+    assert!(
+      format!("{:?}", error).contains("invalid CA certificate"),
+      "{:?}",
+      error
+    );
+  }
+}
@@ -0,0 +1,491 @@
+use proc_macro::TokenStream;
+use proc_macro2::{Ident, Span, TokenStream as TokenStream2};
+use quote::quote;
+use syn::{
+  parse_macro_input, Attribute, Data, DeriveInput, Error, Fields, GenericArgument, Lit, Meta,
+  NestedMeta, PathArguments, Result, Type,
+};
+
+/// Derives `grafana_plugin_sdk::TableSchema` for a row struct.
+///
+/// Each named field becomes a column, named as serde serializes it, so `#[serde(rename)]`,
+/// `#[serde(rename_all)]` and `#[serde(skip)]` apply to the schema too. `Option<T>` fields are
+/// nullable and `Vec<String>` fields are lists of strings. Chrono timestamps (`DateTime<Tz>`
+/// and `NaiveDateTime`) are stored with nanosecond precision and days (`NaiveDate`) as
+/// timestamps in seconds. Fields can be annotated with:
+///
+/// * `#[table(filter)]` to push equality filters on the column down to the datasource, or
+///   `#[table(filter(gt_eq, lt))]` to push down the listed comparisons instead, out of `eq`,
+///   `not_eq`, `lt`, `lt_eq`, `gt`, `gt_eq` and `like`
+/// * `#[table(required)]` to require an equality filter on the column
+/// * `#[table(time)]` to apply the dashboard time range to the column
+/// * `#[table(sortable)]` to advertise that the datasource can sort by the column
+/// * `#[table(key)]` to identify rows by the column when refreshing them incrementally
+/// * `#[table(watermark)]` to refresh rows incrementally, passing the latest value of the
+///   column to the datasource in the `since` option
+/// * `#[table(skip)]` to leave the field out of the schema
+#[proc_macro_derive(GrafanaTable, attributes(table))]
+pub fn derive_grafana_table(input: TokenStream) -> TokenStream {
+  let input = parse_macro_input!(input as DeriveInput);
+  match expand(input) {
+    Ok(tokens) => tokens.into(),
+    Err(e) => e.to_compile_error().into(),
+  }
+}
+
+#[derive(Default)]
+struct Column {
+  name: String,
+  data_type: TokenStream2,
+  nullable: bool,
+  filter: bool,
+  operators: Vec<Ident>,
+  required: bool,
+  sortable: bool,
+  time: bool,
+  key: bool,
+  watermark: bool,
+}
+
+fn expand(input: DeriveInput) -> Result<TokenStream2> {
+  let fields = match &input.data {
+    Data::Struct(data) => match &data.fields {
+      Fields::Named(fields) => &fields.named,
+      _ => {
+        return Err(Error::new_spanned(
+          &input,
+          "GrafanaTable can only be derived for structs with named fields",
+        ))
+      }
+    },
+    _ => {
+      return Err(Error::new_spanned(
+        &input,
+        "GrafanaTable can only be derived for structs",
+      ))
+    }
+  };
+
+  let rename_all = rename_rule(&input.attrs)?;
+  let mut columns = Vec::new();
+
+  for field in fields.iter() {
+    let name = field.ident.as_ref().unwrap().to_string();
+    let name = name.trim_start_matches("r#");
+    let (name, mut skip) = match serde_field(&field.attrs)? {
+      SerdeField::Skip => (String::new(), true),
+      SerdeField::Rename(name) => (name, false),
+      SerdeField::Default => (rename_all.apply(name), false),
+    };
+    let mut column = Column {
+      name,
+      ..Default::default()
+    };
+
+    for attr in field.attrs.iter().filter(|a| a.path.is_ident("table")) {
+      let list = match attr.parse_meta()? {
+        Meta::List(list) => list,
+        meta => return Err(Error::new_spanned(meta, "expected #[table(...)]")),
+      };
+
+      for nested in list.nested.iter() {
+        match nested {
+          NestedMeta::Meta(Meta::Path(p)) if p.is_ident("filter") => column.filter = true,
+          NestedMeta::Meta(Meta::List(l)) if l.path.is_ident("filter") => {
+            column.filter = true;
+            for op in l.nested.iter() {
+              column.operators.push(operator(op)?);
+            }
+          }
+          NestedMeta::Meta(Meta::Path(p)) if p.is_ident("required") => {
+            column.filter = true;
+            column.required = true;
+          }
+          NestedMeta::Meta(Meta::Path(p)) if p.is_ident("time") => column.time = true,
+          NestedMeta::Meta(Meta::Path(p)) if p.is_ident("sortable") => column.sortable = true,
+          NestedMeta::Meta(Meta::Path(p)) if p.is_ident("key") => column.key = true,
+          NestedMeta::Meta(Meta::Path(p)) if p.is_ident("watermark") => column.watermark = true,
+          NestedMeta::Meta(Meta::Path(p)) if p.is_ident("skip") => skip = true,
+          nested => return Err(Error::new_spanned(nested, "unknown table attribute")),
+        }
+      }
+    }
+
+    if skip {
+      continue;
+    }
+
+    // required filters are equality filters, whatever else the column supports
+    if column.required
+      && !column.operators.is_empty()
+      && !column.operators.iter().any(|o| o == "Eq")
+    {
+      column
+        .operators
+        .insert(0, Ident::new("Eq", Span::call_site()));
+    }
+
+    let (ty, nullable) = match option_inner(&field.ty) {
+      Some(inner) => (inner, true),
+      None => (&field.ty, false),
+    };
+    column.nullable = nullable;
+    column.data_type = data_type(ty)?;
+
+    if column.time && !is_timestamp(ty) {
+      return Err(Error::new_spanned(
+        &field.ty,
+        "the time column must be a chrono timestamp",
+      ));
+    }
+
+    if column.watermark && !is_timestamp(ty) {
+      return Err(Error::new_spanned(
+        &field.ty,
+        "the watermark column must be a chrono timestamp",
+      ));
+    }
+
+    columns.push(column);
+  }
+
+  if columns.iter().filter(|c| c.time).count() > 1 {
+    return Err(Error::new_spanned(
+      &input.ident,
+      "only one field can be the time column",
+    ));
+  }
+
+  if columns.iter().filter(|c| c.watermark).count() > 1 {
+    return Err(Error::new_spanned(
+      &input.ident,
+      "only one field can be the watermark column",
+    ));
+  }
+
+  let key: Vec<String> = columns
+    .iter()
+    .filter(|c| c.key)
+    .map(|c| c.name.clone())
+    .collect();
+
+  let incremental = match columns.iter().find(|c| c.watermark) {
+    Some(_) if key.is_empty() => {
+      return Err(Error::new_spanned(
+        &input.ident,
+        "incremental tables need at least one #[table(key)] field",
+      ))
+    }
+    Some(c) => {
+      let watermark = &c.name;
+      quote! {
+        Some(::grafana_plugin_sdk::IncrementalCapability {
+          key: vec![#(#key.to_owned()),*],
+          watermark: #watermark.to_owned(),
+        })
+      }
+    }
+    None => quote! { None },
+  };
+
+  let ident = &input.ident;
+  let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+  let fields = columns.iter().map(|c| {
+    let name = &c.name;
+    let data_type = &c.data_type;
+    let nullable = c.nullable;
+    quote! {
+      ::grafana_plugin_sdk::arrow::datatypes::Field::new(#name, #data_type, #nullable)
+    }
+  });
+
+  let filters = columns.iter().filter(|c| c.filter).map(|c| {
+    let name = &c.name;
+    let operators = &c.operators;
+    match (c.required, operators.is_empty()) {
+      (true, true) => quote! { ::grafana_plugin_sdk::FilterCapability::required(#name) },
+      (false, true) => quote! { ::grafana_plugin_sdk::FilterCapability::eq(#name) },
+      (required, false) => quote! {
+        ::grafana_plugin_sdk::FilterCapability {
+          column: #name.to_owned(),
+          operators: vec![#(::grafana_plugin_sdk::FilterOperator::#operators),*],
+          required: #required,
+        }
+      },
+    }
+  });
+
+  let sortable = columns
+    .iter()
+    .filter(|c| c.sortable)
+    .map(|c| c.name.clone());
+
+  let time_column = match columns.iter().find(|c| c.time) {
+    Some(c) => {
+      let name = &c.name;
+      quote! { Some(#name.to_owned()) }
+    }
+    None => quote! { None },
+  };
+
+  Ok(quote! {
+    impl #impl_generics ::grafana_plugin_sdk::TableSchema for #ident #ty_generics #where_clause {
+      fn table_schema() -> ::grafana_plugin_sdk::arrow::datatypes::SchemaRef {
+        ::std::sync::Arc::new(::grafana_plugin_sdk::arrow::datatypes::Schema::new(vec![
+          #(#fields),*
+        ]))
+      }
+
+      fn capabilities() -> ::grafana_plugin_sdk::TableCapabilities {
+        ::grafana_plugin_sdk::TableCapabilities {
+          filters: vec![#(#filters),*],
+          sortable: vec![#(#sortable.to_owned()),*],
+          limit: false,
+          projection: false,
+          time_column: #time_column,
+          incremental: #incremental,
+        }
+      }
+    }
+  })
+}
+
+/// How serde renames the fields of a struct, from `#[serde(rename_all = "...")]`.
+#[derive(Clone, Copy)]
+enum RenameRule {
+  None,
+  Lower,
+  Upper,
+  Pascal,
+  Camel,
+  ScreamingSnake,
+  Kebab,
+  ScreamingKebab,
+}
+
+impl RenameRule {
+  /// The name serde serializes a snake case field with.
+  fn apply(self, field: &str) -> String {
+    let pascal = || {
+      field
+        .split('_')
+        .map(|word| {
+          let mut chars = word.chars();
+          match chars.next() {
+            Some(first) => first.to_ascii_uppercase().to_string() + chars.as_str(),
+            None => String::new(),
+          }
+        })
+        .collect::<String>()
+    };
+
+    match self {
+      RenameRule::None | RenameRule::Lower => field.to_owned(),
+      RenameRule::Upper | RenameRule::ScreamingSnake => field.to_ascii_uppercase(),
+      RenameRule::Pascal => pascal(),
+      RenameRule::Camel => {
+        let pascal = pascal();
+        let mut chars = pascal.chars();
+        match chars.next() {
+          Some(first) => first.to_ascii_lowercase().to_string() + chars.as_str(),
+          None => pascal,
+        }
+      }
+      RenameRule::Kebab => field.replace('_', "-"),
+      RenameRule::ScreamingKebab => field.replace('_', "-").to_ascii_uppercase(),
+    }
+  }
+}
+
+/// What the serde attributes of a field change about its column.
+enum SerdeField {
+  Default,
+  Rename(String),
+  Skip,
+}
+
+/// The `#[serde(...)]` attributes of a struct or field.
+fn serde_attributes(attrs: &[Attribute]) -> Result<Vec<NestedMeta>> {
+  let mut nested = vec![];
+  for attr in attrs.iter().filter(|a| a.path.is_ident("serde")) {
+    if let Meta::List(list) = attr.parse_meta()? {
+      nested.extend(list.nested);
+    }
+  }
+  Ok(nested)
+}
+
+/// The name serde serializes with, from `name = "..."` or `name(serialize = "...")`.
+fn serialized_name(meta: &Meta) -> Result<Option<String>> {
+  let value = match meta {
+    Meta::NameValue(v) => Some(&v.lit),
+    Meta::List(l) => l.nested.iter().find_map(|n| match n {
+      NestedMeta::Meta(Meta::NameValue(v)) if v.path.is_ident("serialize") => Some(&v.lit),
+      _ => None,
+    }),
+    Meta::Path(_) => None,
+  };
+
+  match value {
+    Some(Lit::Str(s)) => Ok(Some(s.value())),
+    Some(lit) => Err(Error::new_spanned(lit, "expected a string")),
+    None => Ok(None),
+  }
+}
+
+fn rename_rule(attrs: &[Attribute]) -> Result<RenameRule> {
+  let mut rule = RenameRule::None;
+  for nested in serde_attributes(attrs)? {
+    let meta = match &nested {
+      NestedMeta::Meta(m) if m.path().is_ident("rename_all") => m,
+      _ => continue,
+    };
+
+    rule = match serialized_name(meta)?.as_deref() {
+      None => continue,
+      Some("lowercase") | Some("snake_case") => RenameRule::Lower,
+      Some("UPPERCASE") => RenameRule::Upper,
+      Some("PascalCase") => RenameRule::Pascal,
+      Some("camelCase") => RenameRule::Camel,
+      Some("SCREAMING_SNAKE_CASE") => RenameRule::ScreamingSnake,
+      Some("kebab-case") => RenameRule::Kebab,
+      Some("SCREAMING-KEBAB-CASE") => RenameRule::ScreamingKebab,
+      // serde rejects unknown rules itself
+      Some(_) => continue,
+    };
+  }
+  Ok(rule)
+}
+
+fn serde_field(attrs: &[Attribute]) -> Result<SerdeField> {
+  let mut field = SerdeField::Default;
+  for nested in serde_attributes(attrs)? {
+    match &nested {
+      NestedMeta::Meta(Meta::Path(p)) if p.is_ident("skip") || p.is_ident("skip_serializing") => {
+        return Ok(SerdeField::Skip)
+      }
+      // the columns of a flattened struct can't be known here
+      NestedMeta::Meta(Meta::Path(p)) if p.is_ident("flatten") => {
+        return Err(Error::new_spanned(
+          p,
+          "GrafanaTable doesn't support flattened fields",
+        ))
+      }
+      NestedMeta::Meta(m) if m.path().is_ident("rename") => {
+        if let Some(name) = serialized_name(m)? {
+          field = SerdeField::Rename(name);
+        }
+      }
+      _ => {}
+    }
+  }
+  Ok(field)
+}
+
+/// The `FilterOperator` variant of an operator in `#[table(filter(...))]`.
+fn operator(nested: &NestedMeta) -> Result<Ident> {
+  let name = match nested {
+    NestedMeta::Meta(Meta::Path(p)) => p.get_ident().map(|i| i.to_string()),
+    _ => None,
+  };
+
+  let variant = match name.as_deref() {
+    Some("eq") => "Eq",
+    Some("not_eq") => "NotEq",
+    Some("lt") => "Lt",
+    Some("lt_eq") => "LtEq",
+    Some("gt") => "Gt",
+    Some("gt_eq") => "GtEq",
+    Some("like") => "Like",
+    _ => return Err(Error::new_spanned(nested, "unknown filter operator")),
+  };
+  Ok(Ident::new(variant, Span::call_site()))
+}
+
+fn last_segment(ty: &Type) -> Option<&syn::PathSegment> {
+  match ty {
+    Type::Path(p) if p.qself.is_none() => p.path.segments.last(),
+    _ => None,
+  }
+}
+
+fn option_inner(ty: &Type) -> Option<&Type> {
+  type_argument(ty, "Option")
+}
+
+/// The type argument of `wrapper<T>`, if `ty` is one.
+fn type_argument<'a>(ty: &'a Type, wrapper: &str) -> Option<&'a Type> {
+  let segment = last_segment(ty)?;
+  if segment.ident != wrapper {
+    return None;
+  }
+
+  match &segment.arguments {
+    PathArguments::AngleBracketed(args) => match args.args.first() {
+      Some(GenericArgument::Type(inner)) => Some(inner),
+      _ => None,
+    },
+    _ => None,
+  }
+}
+
+fn is_timestamp(ty: &Type) -> bool {
+  matches!(
+    last_segment(ty).map(|s| s.ident.to_string()).as_deref(),
+    Some("DateTime") | Some("NaiveDateTime") | Some("NaiveDate")
+  )
+}
+
+fn data_type(ty: &Type) -> Result<TokenStream2> {
+  let datatypes = quote! { ::grafana_plugin_sdk::arrow::datatypes };
+
+  let name = match last_segment(ty) {
+    Some(segment) => segment.ident.to_string(),
+    None => return Err(Error::new_spanned(ty, "unsupported column type")),
+  };
+
+  Ok(match name.as_str() {
+    "bool" => quote! { #datatypes::DataType::Boolean },
+    "i8" => quote! { #datatypes::DataType::Int8 },
+    "i16" => quote! { #datatypes::DataType::Int16 },
+    "i32" => quote! { #datatypes::DataType::Int32 },
+    "i64" => quote! { #datatypes::DataType::Int64 },
+    "u8" => quote! { #datatypes::DataType::UInt8 },
+    "u16" => quote! { #datatypes::DataType::UInt16 },
+    "u32" => quote! { #datatypes::DataType::UInt32 },
+    "u64" => quote! { #datatypes::DataType::UInt64 },
+    "f32" => quote! { #datatypes::DataType::Float32 },
+    "f64" => quote! { #datatypes::DataType::Float64 },
+    "String" => quote! { #datatypes::DataType::Utf8 },
+    // the items of lists are nullable, as arrow's list builders make them
+    "Vec" => {
+      let item = type_argument(ty, "Vec");
+      let item = item.map(|i| option_inner(i).unwrap_or(i));
+      match item
+        .and_then(last_segment)
+        .map(|s| s.ident.to_string())
+        .as_deref()
+      {
+        Some("String") => quote! {
+          #datatypes::DataType::List(Box::new(
+            #datatypes::Field::new("item", #datatypes::DataType::Utf8, true),
+          ))
+        },
+        _ => {
+          return Err(Error::new_spanned(
+            ty,
+            "only lists of strings are supported",
+          ))
+        }
+      }
+    }
+    "DateTime" | "NaiveDateTime" => {
+      quote! { #datatypes::DataType::Timestamp(#datatypes::TimeUnit::Nanosecond, None) }
+    }
+    "NaiveDate" => {
+      quote! { #datatypes::DataType::Timestamp(#datatypes::TimeUnit::Second, None) }
+    }
+    _ => return Err(Error::new_spanned(ty, "unsupported column type")),
+  })
+}
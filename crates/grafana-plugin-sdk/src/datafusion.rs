@@ -1,9 +1,45 @@
+mod cache;
+mod capabilities;
+mod context;
 mod datasource;
+mod decoder;
+mod disk_cache;
+mod encoder;
 mod execution_plan;
+mod fan_out;
+mod incremental;
+mod pagination;
+mod retry;
+mod statistics;
 mod stream;
 mod table_provider;
+mod time_range;
 
-pub use datasource::DataSource;
+pub use cache::{CacheConfig, CacheStats, CachedDataSource};
+pub use capabilities::{
+  column_requested, FilterCapability, FilterOperator, IncrementalCapability, TableCapabilities,
+  COLUMNS_OPTION, LIMIT_OPTION, SINCE_OPTION,
+};
+pub(crate) use capabilities::{pushed_filter, pushed_options};
+pub use context::current_plugin_context;
+pub(crate) use context::{
+  current_query_context, Diagnostics, InvalidRows, Notice, QueryContext, QUERY_CONTEXT,
+};
+pub use datasource::{
+  BatchStream, DataSource, RowDataSource, Rows, StreamingDataSource, TableSchema,
+};
+pub(crate) use decoder::decode;
+pub use decoder::PayloadFormat;
+pub use disk_cache::DiskCacheConfig;
+pub(crate) use encoder::encode;
 pub(crate) use execution_plan::JSONExec;
-pub(crate) use stream::MemoryStream;
+pub use fan_out::{FanOut, Partitioner};
+pub(crate) use incremental::{merge, watermark};
+pub use pagination::{Page, PageToken, PagedDataSource, Pages};
+pub use retry::{RateLimitStats, RateLimiter, RetryDataSource, RetryPolicy, RetryableError};
+pub(crate) use statistics::{LearnedStatistics, StatisticsCollector, StatisticsKey};
+pub(crate) use stream::ChannelStream;
 pub use table_provider::JSONTableProvider;
+pub use time_range::TimeRange;
+
+pub use ::datafusion::datasource::datasource::{ColumnStatistics, Statistics};
@@ -0,0 +1,535 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, Result};
+use arrow::datatypes::SchemaRef;
+use arrow::record_batch::RecordBatch;
+use async_trait::async_trait;
+use futures::future::{BoxFuture, FutureExt, Shared};
+use futures::stream::{self, StreamExt, TryStreamExt};
+use serde::Serialize;
+
+use crate::datafusion::{
+  current_query_context, merge, watermark, BatchStream, Diagnostics, DiskCacheConfig, Notice,
+  QueryContext, Statistics, StreamingDataSource, TableCapabilities, TimeRange, QUERY_CONTEXT,
+  SINCE_OPTION,
+};
+use crate::plugin::Reporter;
+
+#[derive(Debug, Clone)]
+pub struct CacheConfig {
+  /// How long fetched results are served without refetching. Dashboard time ranges are also
+  /// widened to multiples of it, so that relative ranges share results until they expire.
+  pub ttl: Duration,
+  /// How long after `ttl` expired results are still served while they are refetched in the
+  /// background.
+  pub stale_while_revalidate: Duration,
+  pub max_entries: usize,
+  /// The approximate memory used by the cached record batches.
+  pub max_bytes: usize,
+  /// Keep separate entries for each grafana user, for sources that fetch on their behalf.
+  pub per_user: bool,
+  /// How long incremental datasources only fetch the rows changed since the previous fetch,
+  /// before the results are fetched in full again. Rows that changed so that they no longer
+  /// match the filters of a query are only dropped then.
+  pub max_incremental_age: Duration,
+  /// Fetch without the dashboard time range so that one entry serves every range.
+  ///
+  /// Only correct for sources that don't use the time range or that declare a time column,
+  /// which the results are filtered on after they are read from the cache.
+  pub ignore_time_range: bool,
+  /// Also keep results on disk, where they are read from after the plugin restarts, for as long
+  /// as they would have been cached in memory.
+  pub disk: Option<DiskCacheConfig>,
+}
+
+impl Default for CacheConfig {
+  fn default() -> Self {
+    Self {
+      ttl: Duration::from_secs(600),
+      stale_while_revalidate: Duration::from_secs(0),
+      max_entries: 128,
+      max_bytes: 256 * 1024 * 1024,
+      per_user: false,
+      max_incremental_age: Duration::from_secs(3600),
+      ignore_time_range: false,
+      disk: None,
+    }
+  }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CacheStats {
+  pub hits: u64,
+  /// Expired results that were served while they were refetched.
+  pub stale_hits: u64,
+  pub misses: u64,
+  /// Misses that were read from the disk cache instead of the datasource.
+  pub disk_hits: u64,
+  /// Disk cache files that could not be read or written.
+  pub disk_errors: u64,
+  /// Fetches that only requested the rows changed since the previous one.
+  pub incremental_refreshes: u64,
+  /// Queries that waited on a fetch started by another query instead of fetching themselves.
+  pub coalesced: u64,
+  pub evictions: u64,
+  pub errors: u64,
+  pub entries: usize,
+  pub bytes: usize,
+}
+
+/// Everything that can change the results of a fetch.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct CacheKey {
+  options: BTreeMap<String, String>,
+  time_range: Option<TimeRange>,
+  instance: Option<(i64, i64, i64)>,
+  /// A hash of the instance's secrets, so results fetched with one set of credentials are
+  /// never served for another, even if the instance wasn't updated in between.
+  credentials: Option<u64>,
+  user: Option<String>,
+  skip_invalid_rows: bool,
+  batch_size: usize,
+}
+
+#[derive(Debug, Clone)]
+struct Fetched {
+  batches: Arc<Vec<RecordBatch>>,
+  notices: Vec<Notice>,
+  /// When the results were last fetched in full rather than incrementally.
+  full_fetch_at: Instant,
+}
+
+type Fetch = Shared<BoxFuture<'static, std::result::Result<Fetched, Arc<anyhow::Error>>>>;
+
+enum Lookup {
+  Cached(Fetched),
+  Pending(Fetch),
+}
+
+struct Entry {
+  fetched: Fetched,
+  bytes: usize,
+  fetched_at: Instant,
+  used_at: Instant,
+}
+
+#[derive(Default)]
+struct CacheState {
+  entries: HashMap<CacheKey, Entry>,
+  in_flight: HashMap<CacheKey, Fetch>,
+  stats: CacheStats,
+}
+
+impl CacheState {
+  fn insert(&mut self, key: CacheKey, fetched: Fetched, config: &CacheConfig) {
+    let bytes = fetched
+      .batches
+      .iter()
+      .flat_map(|b| b.columns())
+      .map(|c| c.get_array_memory_size())
+      .sum();
+
+    if let Some(previous) = self.entries.remove(&key) {
+      self.stats.bytes -= previous.bytes;
+    }
+
+    // results that could never fit are not cached at all
+    if bytes > config.max_bytes || config.max_entries == 0 {
+      self.stats.entries = self.entries.len();
+      return;
+    }
+
+    let now = Instant::now();
+    self.entries.insert(
+      key,
+      Entry {
+        fetched,
+        bytes,
+        fetched_at: now,
+        used_at: now,
+      },
+    );
+    self.stats.bytes += bytes;
+
+    while self.entries.len() > config.max_entries || self.stats.bytes > config.max_bytes {
+      let oldest = self
+        .entries
+        .iter()
+        .min_by_key(|(_, e)| e.used_at)
+        .map(|(k, _)| k.clone());
+
+      match oldest.and_then(|k| self.entries.remove(&k)) {
+        Some(entry) => {
+          self.stats.bytes -= entry.bytes;
+          self.stats.evictions += 1;
+        }
+        None => break,
+      }
+    }
+
+    self.stats.entries = self.entries.len();
+  }
+}
+
+/// Caches the record batches fetched by a datasource, keyed on the pushed down options.
+///
+/// Concurrent queries for the same options share a single fetch.
+#[derive(Clone)]
+pub struct CachedDataSource<D> {
+  datasource: Arc<D>,
+  config: CacheConfig,
+  state: Arc<Mutex<CacheState>>,
+}
+
+impl<D> fmt::Debug for CachedDataSource<D>
+where
+  D: fmt::Debug,
+{
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_struct("CachedDataSource")
+      .field("datasource", &self.datasource)
+      .field("config", &self.config)
+      .finish()
+  }
+}
+
+impl<D> CachedDataSource<D>
+where
+  D: StreamingDataSource,
+{
+  pub fn new(datasource: D, config: CacheConfig) -> Self {
+    Self {
+      datasource: Arc::new(datasource),
+      config,
+      state: Arc::new(Mutex::new(CacheState::default())),
+    }
+  }
+
+  pub fn stats(&self) -> CacheStats {
+    self
+      .state
+      .lock()
+      .map(|s| s.stats.clone())
+      .unwrap_or_default()
+  }
+
+  /// Drops every cached result, in memory and on disk. Fetches that are in flight still
+  /// complete.
+  pub fn invalidate(&self) {
+    // the files would otherwise be read back by the next miss
+    let cleared = self.config.disk.as_ref().map(|disk| disk.clear());
+
+    if let Ok(mut state) = self.state.lock() {
+      state.entries.clear();
+      state.stats.entries = 0;
+      state.stats.bytes = 0;
+      if let Some(Err(_)) = cleared {
+        state.stats.disk_errors += 1;
+      }
+    }
+  }
+
+  fn key(
+    &self,
+    options: &HashMap<String, String>,
+    time_range: Option<TimeRange>,
+    context: &QueryContext,
+  ) -> CacheKey {
+    let plugin_context = context.plugin_context.as_ref();
+    let instance = plugin_context.map(|c| match &c.data_source_instance_settings {
+      Some(s) => (c.org_id, s.id, s.last_updated_ms),
+      None => (c.org_id, 0, 0),
+    });
+    let credentials = plugin_context
+      .and_then(|c| c.data_source_instance_settings.as_ref())
+      .map(|s| {
+        let secrets: BTreeMap<_, _> = s.decrypted_secure_json_data.iter().collect();
+        let mut hasher = DefaultHasher::new();
+        secrets.hash(&mut hasher);
+        hasher.finish()
+      });
+    let user = match self.config.per_user {
+      true => plugin_context.and_then(|c| c.user.as_ref().map(|u| u.login.clone())),
+      false => None,
+    };
+
+    CacheKey {
+      options: options.clone().into_iter().collect(),
+      time_range,
+      instance,
+      credentials,
+      user,
+      skip_invalid_rows: context.skip_invalid_rows,
+      batch_size: context.batch_size(),
+    }
+  }
+
+  /// Starts fetching in a task of its own, so the results are cached even if the query that
+  /// started it is cancelled.
+  ///
+  /// If the datasource is incremental, only the rows changed since the `previous` results are
+  /// fetched and merged into them. Otherwise results are read from the disk cache if
+  /// `read_disk` is set, which it only is when nothing was cached in memory, since the files
+  /// are never newer than the expired results they were written with.
+  fn start_fetch(
+    &self,
+    key: CacheKey,
+    options: HashMap<String, String>,
+    time_range: Option<TimeRange>,
+    context: QueryContext,
+    previous: Option<Fetched>,
+    read_disk: bool,
+  ) -> Fetch {
+    let datasource = Arc::clone(&self.datasource);
+    let state = Arc::clone(&self.state);
+    let config = self.config.clone();
+    let schema = self.datasource.schema();
+
+    // the key is stored in the files, so it includes the table columns too
+    let disk_key = format!("{:?} {:?}", schema.fields(), key);
+
+    // notices are kept with the results and raised again by every query that reads them
+    let context = QueryContext {
+      diagnostics: Diagnostics::default(),
+      ..context
+    };
+
+    let fetch = async move {
+      let mut disk_errors = 0;
+
+      let incremental = match (previous, datasource.capabilities().incremental) {
+        (Some(previous), Some(capability)) => {
+          match watermark(&previous.batches, &capability.watermark) {
+            Ok(Some(since)) => Some((previous, capability.key, since)),
+            _ => None,
+          }
+        }
+        _ => None,
+      };
+
+      let from_disk = match (&config.disk, read_disk && incremental.is_none()) {
+        (Some(disk), true) => {
+          let (disk, disk_key, schema) = (disk.clone(), disk_key.clone(), schema.clone());
+          let ttl = config.ttl;
+          match tokio::task::spawn_blocking(move || disk.read(&disk_key, &schema, ttl)).await {
+            Ok(Ok(read)) => read,
+            _ => {
+              disk_errors += 1;
+              None
+            }
+          }
+        }
+        _ => None,
+      };
+
+      let incremental_refresh = incremental.is_some();
+      let full_fetch_at = incremental
+        .as_ref()
+        .map_or_else(Instant::now, |(previous, _, _)| previous.full_fetch_at);
+      let result = match from_disk {
+        Some((batches, notices)) => Ok((batches, notices, true)),
+        None => {
+          let mut options = options;
+          if let Some((_, _, since)) = &incremental {
+            options.insert(SINCE_OPTION.to_owned(), since.clone());
+          }
+
+          let diagnostics = context.diagnostics.clone();
+          let fetched = QUERY_CONTEXT
+            .scope(context, async move {
+              let stream = datasource.fetch_stream(options, time_range).await?;
+              stream.try_collect::<Vec<RecordBatch>>().await
+            })
+            .await;
+
+          match (fetched, incremental) {
+            (Ok(changed), Some((previous, key, _))) => merge(&previous.batches, changed, &key),
+            (fetched, _) => fetched,
+          }
+          .map(|batches| (batches, diagnostics.notices(), false))
+        }
+      };
+
+      if let (Ok((batches, notices, false)), Some(disk)) = (&result, &config.disk) {
+        let (disk, batches, notices) = (disk.clone(), batches.clone(), notices.clone());
+        let written =
+          tokio::task::spawn_blocking(move || disk.write(&disk_key, &schema, &batches, &notices))
+            .await;
+        if !matches!(written, Ok(Ok(()))) {
+          disk_errors += 1;
+        }
+      }
+
+      let mut state = match state.lock() {
+        Ok(s) => s,
+        Err(_) => return Err(Arc::new(anyhow!("cache lock poisoned"))),
+      };
+      state.in_flight.remove(&key);
+      state.stats.disk_errors += disk_errors;
+
+      match result {
+        Ok((batches, notices, from_disk)) => {
+          if from_disk {
+            state.stats.disk_hits += 1;
+          }
+          if incremental_refresh {
+            state.stats.incremental_refreshes += 1;
+          }
+          let fetched = Fetched {
+            batches: Arc::new(batches),
+            notices,
+            full_fetch_at,
+          };
+          state.insert(key, fetched.clone(), &config);
+          Ok(fetched)
+        }
+        Err(e) => {
+          state.stats.errors += 1;
+          Err(Arc::new(e))
+        }
+      }
+    }
+    .boxed()
+    .shared();
+
+    tokio::spawn(fetch.clone());
+    fetch
+  }
+}
+
+#[async_trait]
+impl<D> StreamingDataSource for CachedDataSource<D>
+where
+  D: StreamingDataSource,
+{
+  async fn fetch_stream(
+    &self,
+    options: HashMap<String, String>,
+    time_range: Option<TimeRange>,
+  ) -> Result<BatchStream> {
+    // time ranges are only passed with a time column, that the rows are filtered on after
+    // they're read, so fetching a wider range lets queries relative to now share results
+    let time_range = match self.config.ignore_time_range {
+      true => None,
+      false => time_range.map(|r| r.align(self.config.ttl)),
+    };
+    let context = current_query_context();
+    let key = self.key(&options, time_range, &context);
+    let incremental = self.datasource.capabilities().incremental.is_some();
+
+    let lookup = {
+      let mut state = self
+        .state
+        .lock()
+        .map_err(|_| anyhow!("cache lock poisoned"))?;
+      let state = &mut *state;
+      let now = Instant::now();
+      let ttl = self.config.ttl;
+      let stale = ttl + self.config.stale_while_revalidate;
+
+      let cached = state
+        .entries
+        .get(&key)
+        .map(|entry| (entry.fetched.clone(), now.duration_since(entry.fetched_at)));
+      // the disk cache only serves results the plugin hasn't fetched since it started
+      let read_disk = cached.is_none();
+
+      // expired results of incremental datasources are refreshed with the rows that changed,
+      // until they're due to be fetched in full again
+      let previous = match &cached {
+        Some((fetched, _))
+          if incremental
+            && now.duration_since(fetched.full_fetch_at) < self.config.max_incremental_age =>
+        {
+          Some(fetched.clone())
+        }
+        _ => None,
+      };
+
+      match cached {
+        Some((fetched, age)) if age < stale => {
+          if let Some(entry) = state.entries.get_mut(&key) {
+            entry.used_at = now;
+          }
+
+          if age < ttl {
+            state.stats.hits += 1;
+          } else {
+            state.stats.stale_hits += 1;
+            if !state.in_flight.contains_key(&key) {
+              let fetch = self.start_fetch(
+                key.clone(),
+                options,
+                time_range,
+                context.clone(),
+                previous,
+                false,
+              );
+              state.in_flight.insert(key, fetch);
+            }
+          }
+          Lookup::Cached(fetched)
+        }
+        _ => match state.in_flight.get(&key) {
+          Some(fetch) => {
+            state.stats.coalesced += 1;
+            Lookup::Pending(fetch.clone())
+          }
+          None => {
+            state.stats.misses += 1;
+            let fetch = self.start_fetch(
+              key.clone(),
+              options,
+              time_range,
+              context.clone(),
+              previous,
+              read_disk,
+            );
+            state.in_flight.insert(key, fetch.clone());
+            Lookup::Pending(fetch)
+          }
+        },
+      }
+    };
+
+    let fetched = match lookup {
+      Lookup::Cached(fetched) => fetched,
+      Lookup::Pending(fetch) => fetch.await.map_err(|e| anyhow!("{:#}", e))?,
+    };
+
+    context.diagnostics.extend(fetched.notices);
+    let batches: Vec<RecordBatch> = fetched.batches.iter().cloned().collect();
+    Ok(stream::iter(batches.into_iter().map(Ok)).boxed())
+  }
+
+  fn schema(&self) -> SchemaRef {
+    self.datasource.schema()
+  }
+
+  fn capabilities(&self) -> TableCapabilities {
+    self.datasource.capabilities()
+  }
+
+  fn statistics(&self) -> Option<Statistics> {
+    self.datasource.statistics()
+  }
+}
+
+impl<D> Reporter for CachedDataSource<D>
+where
+  D: StreamingDataSource,
+{
+  fn kind(&self) -> &'static str {
+    "cache"
+  }
+
+  fn report(&self) -> serde_json::Value {
+    serde_json::to_value(self.stats()).unwrap_or_default()
+  }
+}
@@ -0,0 +1,194 @@
+use std::collections::HashMap;
+
+use arrow::datatypes::SchemaRef;
+use datafusion::logical_plan::{Expr, Operator};
+use datafusion::scalar::ScalarValue;
+use serde::Serialize;
+
+/// The option the query limit is passed to `fetch_results` with, if the datasource supports it.
+pub const LIMIT_OPTION: &str = "limit";
+
+/// The option an incremental fetch is passed the watermark of the previous fetch with, as an
+/// RFC 3339 timestamp.
+pub const SINCE_OPTION: &str = "since";
+
+/// The option the columns read by a query are passed in, separated by commas, if the datasource
+/// supports projection.
+pub const COLUMNS_OPTION: &str = "columns";
+
+/// Whether a query reads `column`, which is always the case for datasources that don't support
+/// projection.
+pub fn column_requested(options: &HashMap<String, String>, column: &str) -> bool {
+  options
+    .get(COLUMNS_OPTION)
+    .map_or(true, |columns| columns.split(',').any(|c| c == column))
+}
+
+/// Splits a `column <op> literal` filter into its parts.
+pub(crate) fn pushed_filter(filter: &Expr) -> Option<(&String, FilterOperator, &ScalarValue)> {
+  match filter {
+    Expr::BinaryExpr { left, op, right } => match (&**left, &**right) {
+      (Expr::Column(name), Expr::Literal(value)) => {
+        FilterOperator::from_operator(op).map(|op| (name, op, value))
+      }
+      _ => None,
+    },
+    _ => None,
+  }
+}
+
+/// The options a datasource is passed for the filters pushed down to it, e.g. `state` for
+/// `state = 'open'`.
+pub(crate) fn pushed_options(filters: &[Expr]) -> HashMap<String, String> {
+  filters
+    .iter()
+    .filter_map(pushed_filter)
+    .map(|(column, op, value)| (op.option_key(column), value.to_string()))
+    .collect()
+}
+
+/// The comparisons a datasource can apply itself when a filter is pushed down to it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum FilterOperator {
+  Eq,
+  NotEq,
+  Lt,
+  LtEq,
+  Gt,
+  GtEq,
+  /// A SQL `LIKE` pattern, e.g. `%flaky%`. It's only pushed down inexactly, so datasources
+  /// may return rows that don't match and DataFusion filters them again.
+  Like,
+}
+
+impl FilterOperator {
+  fn from_operator(op: &Operator) -> Option<Self> {
+    match op {
+      Operator::Eq => Some(FilterOperator::Eq),
+      Operator::NotEq => Some(FilterOperator::NotEq),
+      Operator::Lt => Some(FilterOperator::Lt),
+      Operator::LtEq => Some(FilterOperator::LtEq),
+      Operator::Gt => Some(FilterOperator::Gt),
+      Operator::GtEq => Some(FilterOperator::GtEq),
+      Operator::Like => Some(FilterOperator::Like),
+      _ => None,
+    }
+  }
+
+  /// The key a pushed down filter is passed to `fetch_results` with.
+  ///
+  /// Equality filters use the column name, other comparisons append the operator,
+  /// e.g. `created_at >=` or `title LIKE`.
+  pub fn option_key(&self, column: &str) -> String {
+    let symbol = match self {
+      FilterOperator::Eq => return column.to_owned(),
+      FilterOperator::NotEq => "!=",
+      FilterOperator::Lt => "<",
+      FilterOperator::LtEq => "<=",
+      FilterOperator::Gt => ">",
+      FilterOperator::GtEq => ">=",
+      FilterOperator::Like => "LIKE",
+    };
+    format!("{} {}", column, symbol)
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FilterCapability {
+  pub column: String,
+  pub operators: Vec<FilterOperator>,
+  /// Queries must constrain the column with an equality filter.
+  pub required: bool,
+}
+
+impl FilterCapability {
+  pub fn eq(column: &str) -> Self {
+    Self {
+      column: column.to_owned(),
+      operators: vec![FilterOperator::Eq],
+      required: false,
+    }
+  }
+
+  pub fn required(column: &str) -> Self {
+    Self {
+      required: true,
+      ..Self::eq(column)
+    }
+  }
+}
+
+/// What a datasource can do with a query besides returning rows.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TableCapabilities {
+  pub filters: Vec<FilterCapability>,
+  pub sortable: Vec<String>,
+  /// The datasource accepts the query limit in the `limit` option.
+  pub limit: bool,
+  /// The datasource accepts the columns the query reads in the `columns` option, and may
+  /// leave the others null.
+  pub projection: bool,
+  /// The timestamp column that the dashboard time range is applied to.
+  pub time_column: Option<String>,
+  pub incremental: Option<IncrementalCapability>,
+}
+
+/// Lets a cached datasource fetch only the rows that changed since its previous fetch.
+///
+/// Rows removed from the source are kept until the cached results are evicted.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct IncrementalCapability {
+  /// The columns that identify a row. Fetched rows replace the cached ones with the same key.
+  pub key: Vec<String>,
+  /// A timestamp column that increases whenever a row changes, e.g. `updated_at`.
+  pub watermark: String,
+}
+
+impl TableCapabilities {
+  /// Capabilities declared with a `"filter"` key in the field metadata of `schema`.
+  ///
+  /// This only exists for datasources written before capabilities, which only support
+  /// equality filters.
+  pub fn from_metadata(
+    schema: &SchemaRef,
+    required_filters: Vec<String>,
+    time_column: Option<String>,
+  ) -> Self {
+    let filters = schema
+      .fields()
+      .iter()
+      .filter(|f| {
+        let metadata = f.metadata().as_ref();
+        metadata.map_or(false, |m| m.contains_key("filter")) || required_filters.contains(f.name())
+      })
+      .map(|f| match required_filters.contains(f.name()) {
+        true => FilterCapability::required(f.name()),
+        false => FilterCapability::eq(f.name()),
+      })
+      .collect();
+
+    Self {
+      filters,
+      time_column,
+      ..Default::default()
+    }
+  }
+
+  pub fn supports_filter(&self, column: &str, op: FilterOperator) -> bool {
+    self
+      .filters
+      .iter()
+      .any(|f| f.column == column && f.operators.contains(&op))
+  }
+
+  pub fn required_filters(&self) -> Vec<String> {
+    self
+      .filters
+      .iter()
+      .filter(|f| f.required)
+      .map(|f| f.column.clone())
+      .collect()
+  }
+}
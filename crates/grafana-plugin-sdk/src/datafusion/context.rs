@@ -0,0 +1,132 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use serde::{Deserialize, Serialize};
+
+use crate::datafusion::TimeRange;
+use crate::proto::pluginv2::PluginContext;
+
+const DEFAULT_BATCH_SIZE: usize = 1024;
+const INVALID_ROW_SAMPLES: usize = 5;
+const INVALID_ROW_LENGTH: usize = 200;
+
+tokio::task_local! {
+  // the options of the query currently being planned
+  pub(crate) static QUERY_CONTEXT: QueryContext;
+}
+
+pub(crate) fn current_query_context() -> QueryContext {
+  QUERY_CONTEXT.try_with(|c| c.clone()).unwrap_or_default()
+}
+
+/// The grafana organization, datasource instance and user of the query being executed.
+///
+/// Datasources read the settings of their instance from it, e.g. credentials from its
+/// `decrypted_secure_json_data`.
+pub fn current_plugin_context() -> Option<PluginContext> {
+  QUERY_CONTEXT
+    .try_with(|c| c.plugin_context.clone())
+    .ok()
+    .flatten()
+}
+
+#[derive(Debug, Clone, Default)]
+pub(crate) struct QueryContext {
+  pub time_range: Option<TimeRange>,
+  pub skip_invalid_rows: bool,
+  pub batch_size: Option<usize>,
+  pub diagnostics: Diagnostics,
+  /// The grafana organization, datasource instance and user the query was sent for.
+  pub plugin_context: Option<PluginContext>,
+}
+
+impl QueryContext {
+  /// The number of rows decoded into each record batch.
+  pub fn batch_size(&self) -> usize {
+    self.batch_size.unwrap_or(DEFAULT_BATCH_SIZE).max(1)
+  }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Severity {
+  Warning,
+}
+
+/// A message shown alongside the frames of a query.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct Notice {
+  severity: Severity,
+  text: String,
+}
+
+/// Collects the notices raised while executing a query.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Diagnostics(Arc<Mutex<Vec<Notice>>>);
+
+impl Diagnostics {
+  pub fn push(&self, severity: Severity, text: String) {
+    if let Ok(mut notices) = self.0.lock() {
+      notices.push(Notice { severity, text });
+    }
+  }
+
+  pub fn notices(&self) -> Vec<Notice> {
+    self.0.lock().map(|n| n.clone()).unwrap_or_default()
+  }
+
+  pub fn extend(&self, notices: Vec<Notice>) {
+    if let Ok(mut n) = self.0.lock() {
+      n.extend(notices);
+    }
+  }
+
+  /// The frame metadata expected by grafana, if any notices were raised.
+  pub fn frame_meta(&self) -> Option<HashMap<String, String>> {
+    let notices = self.0.lock().ok()?.clone();
+    if notices.is_empty() {
+      return None;
+    }
+
+    let mut metadata = HashMap::new();
+    metadata.insert(
+      "meta".to_owned(),
+      serde_json::json!({ "notices": notices }).to_string(),
+    );
+    Some(metadata)
+  }
+}
+
+/// Counts the rows skipped while decoding and keeps a sample of them for the query notice.
+#[derive(Debug, Default)]
+pub(crate) struct InvalidRows {
+  count: usize,
+  samples: Vec<String>,
+}
+
+impl InvalidRows {
+  pub fn record(&mut self, row: usize, error: &str, content: &str) {
+    self.count += 1;
+    if self.samples.len() < INVALID_ROW_SAMPLES {
+      let content: String = content.chars().take(INVALID_ROW_LENGTH).collect();
+      self
+        .samples
+        .push(format!("row {}: {}: {}", row, error, content));
+    }
+  }
+
+  pub fn report(self, diagnostics: &Diagnostics) {
+    if self.count == 0 {
+      return;
+    }
+
+    diagnostics.push(
+      Severity::Warning,
+      format!(
+        "skipped {} invalid rows\n{}",
+        self.count,
+        self.samples.join("\n")
+      ),
+    );
+  }
+}
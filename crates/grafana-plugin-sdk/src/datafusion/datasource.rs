@@ -4,13 +4,201 @@ use std::io::{Read, Seek};
 
 use anyhow::Result;
 use arrow::datatypes::SchemaRef;
+use arrow::record_batch::RecordBatch;
 use async_trait::async_trait;
+use futures::stream::{self, BoxStream, StreamExt};
+use serde::Serialize;
+
+use crate::datafusion::{decode, encode, PayloadFormat, Statistics, TableCapabilities, TimeRange};
+
+pub type BatchStream = BoxStream<'static, Result<RecordBatch>>;
+
+/// The columns of a row type, usually implemented with `#[derive(GrafanaTable)]`.
+pub trait TableSchema {
+  fn table_schema() -> SchemaRef;
+
+  fn capabilities() -> TableCapabilities;
+}
 
 #[async_trait]
 pub trait DataSource: Send + Sync + Clone + Debug + 'static {
   type Data: Read + Seek + Send + Sync + Debug + Clone;
 
-  async fn fetch_results(&self, options: HashMap<String, String>) -> Result<Self::Data>;
+  async fn fetch_results(
+    &self,
+    options: HashMap<String, String>,
+    time_range: Option<TimeRange>,
+  ) -> Result<Self::Data>;
+
+  fn schema(&self) -> SchemaRef;
+
+  /// Columns that must be constrained with an equality filter for the table to be queried.
+  ///
+  /// Only used by the default `capabilities`.
+  fn required_filters(&self) -> Vec<String> {
+    vec![]
+  }
+
+  /// The timestamp column that the dashboard time range is applied to.
+  ///
+  /// Only used by the default `capabilities`.
+  fn time_column(&self) -> Option<String> {
+    None
+  }
+
+  /// The filters, limits and time column the datasource supports.
+  ///
+  /// Defaults to equality filters on fields with `"filter"` metadata.
+  fn capabilities(&self) -> TableCapabilities {
+    TableCapabilities::from_metadata(&self.schema(), self.required_filters(), self.time_column())
+  }
+
+  fn format(&self) -> PayloadFormat {
+    PayloadFormat::NdJson
+  }
+
+  /// Estimated table statistics, used instead of the ones observed when the whole table was
+  /// last fetched.
+  fn statistics(&self) -> Option<Statistics> {
+    None
+  }
+}
+
+/// A source that produces record batches as they are fetched instead of a complete payload.
+///
+/// Every `DataSource` is also a `StreamingDataSource` that decodes its payload in `format()`.
+#[async_trait]
+pub trait StreamingDataSource: Send + Sync + Clone + Debug + 'static {
+  async fn fetch_stream(
+    &self,
+    options: HashMap<String, String>,
+    time_range: Option<TimeRange>,
+  ) -> Result<BatchStream>;
+
+  fn schema(&self) -> SchemaRef;
+
+  fn required_filters(&self) -> Vec<String> {
+    vec![]
+  }
+
+  fn time_column(&self) -> Option<String> {
+    None
+  }
+
+  fn capabilities(&self) -> TableCapabilities {
+    TableCapabilities::from_metadata(&self.schema(), self.required_filters(), self.time_column())
+  }
+
+  fn statistics(&self) -> Option<Statistics> {
+    None
+  }
+}
+
+#[async_trait]
+impl<D> StreamingDataSource for D
+where
+  D: DataSource,
+{
+  async fn fetch_stream(
+    &self,
+    options: HashMap<String, String>,
+    time_range: Option<TimeRange>,
+  ) -> Result<BatchStream> {
+    let results = self.fetch_results(options, time_range).await?;
+    let batches = decode(&self.format(), DataSource::schema(self), results)?;
+
+    Ok(stream::iter(batches.into_iter().map(Ok)).boxed())
+  }
+
+  fn schema(&self) -> SchemaRef {
+    DataSource::schema(self)
+  }
+
+  fn required_filters(&self) -> Vec<String> {
+    DataSource::required_filters(self)
+  }
+
+  fn time_column(&self) -> Option<String> {
+    DataSource::time_column(self)
+  }
+
+  fn capabilities(&self) -> TableCapabilities {
+    DataSource::capabilities(self)
+  }
+
+  fn statistics(&self) -> Option<Statistics> {
+    DataSource::statistics(self)
+  }
+}
+
+/// A source that returns rows which are serialized directly into the columns of `schema()`.
+///
+/// Wrap it in `Rows` to register it with a `JSONTableProvider`.
+#[async_trait]
+pub trait RowDataSource: Send + Sync + Clone + Debug + 'static {
+  type Row: Serialize + Send + Sync;
+
+  async fn fetch_rows(
+    &self,
+    options: HashMap<String, String>,
+    time_range: Option<TimeRange>,
+  ) -> Result<Vec<Self::Row>>;
 
   fn schema(&self) -> SchemaRef;
+
+  fn required_filters(&self) -> Vec<String> {
+    vec![]
+  }
+
+  fn time_column(&self) -> Option<String> {
+    None
+  }
+
+  fn capabilities(&self) -> TableCapabilities {
+    TableCapabilities::from_metadata(&self.schema(), self.required_filters(), self.time_column())
+  }
+
+  fn statistics(&self) -> Option<Statistics> {
+    None
+  }
+}
+
+#[derive(Debug, Clone)]
+pub struct Rows<D>(pub D);
+
+#[async_trait]
+impl<D> StreamingDataSource for Rows<D>
+where
+  D: RowDataSource,
+{
+  async fn fetch_stream(
+    &self,
+    options: HashMap<String, String>,
+    time_range: Option<TimeRange>,
+  ) -> Result<BatchStream> {
+    let rows = self.0.fetch_rows(options, time_range).await?;
+    let batches = encode(self.0.schema(), &rows)?;
+
+    Ok(stream::iter(batches.into_iter().map(Ok)).boxed())
+  }
+
+  fn schema(&self) -> SchemaRef {
+    self.0.schema()
+  }
+
+  fn required_filters(&self) -> Vec<String> {
+    self.0.required_filters()
+  }
+
+  fn time_column(&self) -> Option<String> {
+    self.0.time_column()
+  }
+
+  fn capabilities(&self) -> TableCapabilities {
+    self.0.capabilities()
+  }
+
+  fn statistics(&self) -> Option<Statistics> {
+    self.0.statistics()
+  }
 }
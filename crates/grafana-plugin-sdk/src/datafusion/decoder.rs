@@ -0,0 +1,224 @@
+use std::io::{Read, Seek};
+use std::sync::Arc;
+
+use anyhow::{anyhow, Result};
+use arrow::compute::cast;
+use arrow::csv;
+use arrow::datatypes::{DataType, SchemaRef};
+use arrow::ipc::reader::FileReader;
+use arrow::json::reader::Decoder;
+use arrow::record_batch::RecordBatch;
+use parquet::arrow::{ArrowReader, ParquetFileArrowReader};
+use parquet::file::reader::SerializedFileReader;
+use parquet::util::cursor::SliceableCursor;
+use serde_json::Value;
+
+use crate::datafusion::{current_query_context, InvalidRows};
+
+/// The encoding of the payload returned by `DataSource::fetch_results`.
+///
+/// JSON rows are validated against the schema, so only JSON payloads can skip invalid rows.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PayloadFormat {
+  /// Newline-delimited JSON objects.
+  NdJson,
+  /// A JSON array of objects, optionally nested in a document at a dot-separated `records_path`.
+  Json {
+    records_path: Option<String>,
+  },
+  Csv {
+    has_header: bool,
+    delimiter: u8,
+  },
+  /// The Arrow IPC file format.
+  ArrowIpc,
+  Parquet,
+}
+
+impl Default for PayloadFormat {
+  fn default() -> Self {
+    PayloadFormat::NdJson
+  }
+}
+
+pub(crate) fn decode<R>(
+  format: &PayloadFormat,
+  schema: SchemaRef,
+  data: R,
+) -> Result<Vec<RecordBatch>>
+where
+  R: Read + Seek,
+{
+  let batch_size = current_query_context().batch_size();
+
+  match format {
+    PayloadFormat::NdJson => decode_ndjson(schema, data),
+    PayloadFormat::Json { records_path } => decode_json(schema, data, records_path),
+    PayloadFormat::Csv {
+      has_header,
+      delimiter,
+    } => {
+      let reader = csv::ReaderBuilder::new()
+        .with_schema(schema)
+        .has_header(*has_header)
+        .with_delimiter(*delimiter)
+        .with_batch_size(batch_size)
+        .build(data)?;
+
+      Ok(reader.collect::<arrow::error::Result<Vec<RecordBatch>>>()?)
+    }
+    PayloadFormat::ArrowIpc => FileReader::try_new(data)?
+      .map(|batch| conform(batch?, &schema))
+      .collect(),
+    PayloadFormat::Parquet => {
+      let mut data = data;
+      let mut bytes = Vec::new();
+      data.read_to_end(&mut bytes)?;
+
+      let file_reader = SerializedFileReader::new(SliceableCursor::new(Arc::new(bytes)))?;
+      let mut arrow_reader = ParquetFileArrowReader::new(Arc::new(file_reader));
+
+      arrow_reader
+        .get_record_reader(batch_size)?
+        .map(|batch| conform(batch?, &schema))
+        .collect()
+    }
+  }
+}
+
+fn decode_ndjson<R>(schema: SchemaRef, mut data: R) -> Result<Vec<RecordBatch>>
+where
+  R: Read + Seek,
+{
+  let mut content = String::new();
+  data.read_to_string(&mut content)?;
+
+  let records = content
+    .lines()
+    .filter(|line| !line.trim().is_empty())
+    .map(|line| serde_json::from_str::<Value>(line).map_err(|e| (e.to_string(), line.to_owned())));
+
+  decode_records(schema, records)
+}
+
+fn decode_json<R>(
+  schema: SchemaRef,
+  data: R,
+  records_path: &Option<String>,
+) -> Result<Vec<RecordBatch>>
+where
+  R: Read + Seek,
+{
+  let document: Value = serde_json::from_reader(data)?;
+
+  let records = match records_path {
+    Some(path) => {
+      path
+        .split('.')
+        .filter(|key| !key.is_empty())
+        .try_fold(&document, |value, key| {
+          value
+            .get(key)
+            .ok_or_else(|| anyhow!("records path \"{}\" not found in payload", path))
+        })?
+    }
+    None => &document,
+  };
+
+  let records = match records {
+    Value::Array(records) => records.clone(),
+    Value::Object(_) => vec![records.clone()],
+    _ => return Err(anyhow!("expected a JSON array or object of records")),
+  };
+
+  decode_records(schema, records.into_iter().map(Ok))
+}
+
+// each record is either a parsed row or the parse error and the raw row
+fn decode_records<I>(schema: SchemaRef, records: I) -> Result<Vec<RecordBatch>>
+where
+  I: Iterator<Item = std::result::Result<Value, (String, String)>>,
+{
+  let context = current_query_context();
+  let mut invalid = InvalidRows::default();
+  let mut rows = Vec::new();
+
+  for (i, record) in records.enumerate() {
+    let record = record.and_then(|value| match validate(&value, &schema) {
+      Ok(()) => Ok(value),
+      Err(e) => Err((e, value.to_string())),
+    });
+
+    match record {
+      Ok(value) => rows.push(value),
+      Err((error, content)) if context.skip_invalid_rows => {
+        invalid.record(i + 1, error.as_str(), content.as_str())
+      }
+      Err((error, _)) => return Err(anyhow!("invalid row {}: {}", i + 1, error)),
+    }
+  }
+
+  invalid.report(&context.diagnostics);
+
+  // the rows are already parsed, so they're decoded as they are instead of as text
+  let decoder = Decoder::new(schema, context.batch_size(), None);
+  let mut rows = rows.into_iter().map(Ok);
+  let mut batches = Vec::new();
+
+  while let Some(batch) = decoder.next_batch(&mut rows)? {
+    batches.push(batch);
+  }
+
+  Ok(batches)
+}
+
+fn validate(value: &Value, schema: &SchemaRef) -> std::result::Result<(), String> {
+  let row = value
+    .as_object()
+    .ok_or_else(|| "expected a JSON object".to_owned())?;
+
+  for field in schema.fields() {
+    let valid = match row.get(field.name()) {
+      None | Some(Value::Null) => field.is_nullable(),
+      Some(v) => match field.data_type() {
+        DataType::Boolean => v.is_boolean(),
+        DataType::Int8 | DataType::Int16 | DataType::Int32 | DataType::Int64 => v.is_i64(),
+        DataType::UInt8 | DataType::UInt16 | DataType::UInt32 | DataType::UInt64 => v.is_u64(),
+        DataType::Float32 | DataType::Float64 => v.is_number(),
+        DataType::Utf8 | DataType::LargeUtf8 => v.is_string(),
+        DataType::Timestamp(_, _) | DataType::Date32 | DataType::Date64 => v.is_i64(),
+        DataType::List(_) => v.is_array(),
+        _ => true,
+      },
+    };
+
+    if !valid {
+      return Err(format!(
+        "invalid value {} for column {} of type {:?}",
+        row.get(field.name()).unwrap_or(&Value::Null),
+        field.name(),
+        field.data_type()
+      ));
+    }
+  }
+
+  Ok(())
+}
+
+// self-describing formats may order or type their columns differently than the datasource schema
+fn conform(batch: RecordBatch, schema: &SchemaRef) -> Result<RecordBatch> {
+  if batch.schema() == *schema {
+    return Ok(batch);
+  }
+
+  let columns = schema
+    .fields()
+    .iter()
+    .map(|field| {
+      let index = batch.schema().index_of(field.name())?;
+      Ok(cast(batch.column(index), field.data_type())?)
+    })
+    .collect::<Result<Vec<_>>>()?;
+
+  Ok(RecordBatch::try_new(schema.clone(), columns)?)
+}
@@ -0,0 +1,187 @@
+use std::collections::hash_map::DefaultHasher;
+use std::fs::{self, File};
+use std::hash::{Hash, Hasher};
+use std::io::{BufReader, BufWriter, ErrorKind};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use anyhow::Result;
+use arrow::datatypes::{Schema, SchemaRef};
+use arrow::ipc::reader::FileReader;
+use arrow::ipc::writer::FileWriter;
+use arrow::record_batch::RecordBatch;
+
+use crate::datafusion::Notice;
+
+const KEY_METADATA: &str = "cacheKey";
+const NOTICES_METADATA: &str = "cacheNotices";
+
+/// Keeps fetched results in Arrow IPC files, so they outlive the plugin process.
+///
+/// Files are written atomically and can be shared by several plugin processes, but each table
+/// should use a directory of its own.
+#[derive(Debug, Clone)]
+pub struct DiskCacheConfig {
+  pub directory: PathBuf,
+  /// How long files are read back after they were written, at most the `ttl` of the cache.
+  pub ttl: Duration,
+  /// The size of the directory above which the oldest files are removed.
+  pub max_bytes: u64,
+}
+
+impl DiskCacheConfig {
+  pub fn new(directory: impl Into<PathBuf>) -> Self {
+    Self {
+      directory: directory.into(),
+      ttl: Duration::from_secs(24 * 60 * 60),
+      max_bytes: 1024 * 1024 * 1024,
+    }
+  }
+
+  fn path(&self, key: &str) -> PathBuf {
+    let mut hasher = DefaultHasher::new();
+    key.hash(&mut hasher);
+    self
+      .directory
+      .join(format!("{:016x}.arrow", hasher.finish()))
+  }
+
+  /// The batches and notices stored for `key`, if they were written less than `ttl` and
+  /// `max_age` ago.
+  pub(crate) fn read(
+    &self,
+    key: &str,
+    schema: &SchemaRef,
+    max_age: Duration,
+  ) -> Result<Option<(Vec<RecordBatch>, Vec<Notice>)>> {
+    let file = match File::open(self.path(key)) {
+      Ok(f) => f,
+      Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+      Err(e) => return Err(e.into()),
+    };
+
+    let age = file.metadata()?.modified()?.elapsed().unwrap_or_default();
+    if age >= self.ttl.min(max_age) {
+      return Ok(None);
+    }
+
+    let reader = FileReader::try_new(BufReader::new(file))?;
+    let metadata = reader.schema().metadata().clone();
+
+    // file names are hashes, so the key is stored to rule out collisions
+    if metadata.get(KEY_METADATA).map(String::as_str) != Some(key) {
+      return Ok(None);
+    }
+
+    let notices = match metadata.get(NOTICES_METADATA) {
+      Some(n) => serde_json::from_str(n)?,
+      None => vec![],
+    };
+
+    let batches = reader
+      .map(|batch| {
+        Ok(RecordBatch::try_new(
+          Arc::clone(schema),
+          batch?.columns().to_vec(),
+        )?)
+      })
+      .collect::<Result<Vec<RecordBatch>>>()?;
+
+    Ok(Some((batches, notices)))
+  }
+
+  pub(crate) fn write(
+    &self,
+    key: &str,
+    schema: &SchemaRef,
+    batches: &[RecordBatch],
+    notices: &[Notice],
+  ) -> Result<()> {
+    fs::create_dir_all(&self.directory)?;
+
+    let mut metadata = schema.metadata().clone();
+    metadata.insert(KEY_METADATA.to_owned(), key.to_owned());
+    metadata.insert(NOTICES_METADATA.to_owned(), serde_json::to_string(notices)?);
+    let schema = Arc::new(Schema::new_with_metadata(schema.fields().clone(), metadata));
+
+    // other processes only ever see complete files, since renames are atomic
+    let path = self.path(key);
+    let temporary = path.with_extension(format!("{}.tmp", std::process::id()));
+    {
+      let mut writer = FileWriter::try_new(BufWriter::new(File::create(&temporary)?), &schema)?;
+      for batch in batches {
+        writer.write(&RecordBatch::try_new(
+          Arc::clone(&schema),
+          batch.columns().to_vec(),
+        )?)?;
+      }
+      writer.finish()?;
+    }
+    fs::rename(&temporary, &path)?;
+
+    self.evict()
+  }
+
+  /// Removes every cached file of the directory.
+  pub(crate) fn clear(&self) -> Result<()> {
+    let entries = match fs::read_dir(&self.directory) {
+      Ok(entries) => entries,
+      Err(e) if e.kind() == ErrorKind::NotFound => return Ok(()),
+      Err(e) => return Err(e.into()),
+    };
+
+    for entry in entries {
+      let path = entry?.path();
+      if path.extension().and_then(|e| e.to_str()) == Some("arrow") {
+        remove(&path)?;
+      }
+    }
+    Ok(())
+  }
+
+  /// Removes the oldest files until the directory fits in `max_bytes`, along with temporary
+  /// files left behind by processes that exited while writing.
+  fn evict(&self) -> Result<()> {
+    let now = SystemTime::now();
+    let mut files = vec![];
+
+    for entry in fs::read_dir(&self.directory)? {
+      let entry = entry?;
+      let path = entry.path();
+      let metadata = match entry.metadata() {
+        Ok(m) => m,
+        Err(_) => continue,
+      };
+      let modified = metadata.modified()?;
+
+      match path.extension().and_then(|e| e.to_str()) {
+        Some("arrow") => files.push((modified, metadata.len(), path)),
+        Some("tmp") if now.duration_since(modified).unwrap_or_default() > self.ttl => {
+          remove(&path)?
+        }
+        _ => {}
+      }
+    }
+
+    files.sort();
+    let mut total: u64 = files.iter().map(|(_, len, _)| len).sum();
+    for (_, len, path) in files {
+      if total <= self.max_bytes {
+        break;
+      }
+      remove(&path)?;
+      total -= len;
+    }
+
+    Ok(())
+  }
+}
+
+/// Removes a file that another process may have removed already.
+fn remove(path: &Path) -> Result<()> {
+  match fs::remove_file(path) {
+    Err(e) if e.kind() != ErrorKind::NotFound => Err(e.into()),
+    _ => Ok(()),
+  }
+}
@@ -0,0 +1,719 @@
+use std::convert::TryFrom;
+use std::fmt::{self, Display};
+
+use anyhow::{anyhow, Result};
+use arrow::array::{
+  ArrayBuilder, BooleanBuilder, Float32Builder, Float64Builder, Int16Builder, Int32Builder,
+  Int64Builder, Int8Builder, ListBuilder, StringBuilder, TimestampMicrosecondBuilder,
+  TimestampMillisecondBuilder, TimestampNanosecondBuilder, TimestampSecondBuilder, UInt16Builder,
+  UInt32Builder, UInt64Builder, UInt8Builder,
+};
+use arrow::datatypes::{DataType, Field, SchemaRef, TimeUnit};
+use arrow::record_batch::RecordBatch;
+use chrono::{DateTime, NaiveDate, NaiveDateTime};
+use serde::ser::{self, Impossible, Serialize};
+
+use crate::datafusion::{current_query_context, InvalidRows};
+
+/// Converts rows straight into record batches using typed builders for each column of `schema`.
+///
+/// Rows must serialize as structs or maps. Keys that are not in the schema are ignored and
+/// columns missing from a row are null. Sequences are stored in `List<Utf8>` columns. Rows that
+/// don't match the schema fail the query unless it skips invalid rows.
+pub(crate) fn encode<T>(schema: SchemaRef, rows: &[T]) -> Result<Vec<RecordBatch>>
+where
+  T: Serialize,
+{
+  let context = current_query_context();
+  let batch_size = context.batch_size();
+  let mut invalid = InvalidRows::default();
+
+  let batches = rows
+    .chunks(batch_size)
+    .enumerate()
+    .map(|(chunk_index, chunk)| {
+      let mut encoder = RowEncoder::try_new(schema.clone(), chunk.len())?;
+      for (i, row) in chunk.iter().enumerate() {
+        let row_number = chunk_index * batch_size + i + 1;
+        match encoder.append(row) {
+          Ok(()) => {}
+          Err(e) if context.skip_invalid_rows => invalid.record(
+            row_number,
+            e.to_string().as_str(),
+            serde_json::to_string(row).unwrap_or_default().as_str(),
+          ),
+          Err(e) => return Err(anyhow!("invalid row {}: {}", row_number, e)),
+        }
+      }
+      encoder.finish()
+    })
+    .collect::<Result<Vec<RecordBatch>>>()?;
+
+  invalid.report(&context.diagnostics);
+  Ok(batches)
+}
+
+struct RowEncoder {
+  schema: SchemaRef,
+  builders: Vec<Box<dyn ArrayBuilder>>,
+  values: Vec<Scalar>,
+}
+
+impl RowEncoder {
+  fn try_new(schema: SchemaRef, capacity: usize) -> Result<Self> {
+    let builders = schema
+      .fields()
+      .iter()
+      .map(|field| {
+        new_builder(field.data_type(), capacity).ok_or_else(|| {
+          anyhow!(
+            "unsupported data type {:?} for column {}",
+            field.data_type(),
+            field.name()
+          )
+        })
+      })
+      .collect::<Result<Vec<_>>>()?;
+
+    Ok(Self {
+      values: vec![Scalar::Null; builders.len()],
+      schema,
+      builders,
+    })
+  }
+
+  /// Appends a row, leaving the builders untouched if it doesn't match the schema.
+  fn append<T>(&mut self, row: &T) -> Result<()>
+  where
+    T: Serialize,
+  {
+    self.values.iter_mut().for_each(|v| *v = Scalar::Null);
+    row.serialize(RowSerializer {
+      encoder: self,
+      key: None,
+    })?;
+
+    for (field, value) in self.schema.fields().iter().zip(self.values.iter()) {
+      check(field, value)?;
+    }
+
+    for i in 0..self.builders.len() {
+      let value = std::mem::replace(&mut self.values[i], Scalar::Null);
+      self.append_value(i, value)?;
+    }
+
+    Ok(())
+  }
+
+  /// Serializes the value of a column. Fields that aren't columns are skipped unserialized, as
+  /// they may be of types no column holds, e.g. nested structs.
+  fn append_field<T: ?Sized + Serialize>(
+    &mut self,
+    name: &str,
+    value: &T,
+  ) -> Result<(), SerializeError> {
+    if let Ok(index) = self.schema.index_of(name) {
+      self.values[index] = value.serialize(ScalarSerializer)?;
+    }
+    Ok(())
+  }
+
+  fn append_value(&mut self, index: usize, value: Scalar) -> Result<()> {
+    let field = self.schema.field(index);
+    let builder = self.builders[index].as_any_mut();
+
+    if matches!(value, Scalar::Null) && !field.is_nullable() {
+      return Err(anyhow!("column {} is not nullable", field.name()));
+    }
+
+    let mismatch = |value: &Scalar| {
+      anyhow!(
+        "unable to store {:?} in column {} of type {:?}",
+        value,
+        field.name(),
+        field.data_type()
+      )
+    };
+
+    macro_rules! append_primitive {
+      ($builder:ty, $convert:expr) => {{
+        let builder = builder.downcast_mut::<$builder>().unwrap();
+        match value {
+          Scalar::Null => builder.append_null()?,
+          ref v => match ($convert)(v) {
+            Some(v) => builder.append_value(v)?,
+            None => return Err(mismatch(v)),
+          },
+        }
+      }};
+    }
+
+    match field.data_type() {
+      DataType::Boolean => append_primitive!(BooleanBuilder, Scalar::as_bool),
+      DataType::Int8 => append_primitive!(Int8Builder, |v: &Scalar| v.as_int::<i8>()),
+      DataType::Int16 => append_primitive!(Int16Builder, |v: &Scalar| v.as_int::<i16>()),
+      DataType::Int32 => append_primitive!(Int32Builder, |v: &Scalar| v.as_int::<i32>()),
+      DataType::Int64 => append_primitive!(Int64Builder, |v: &Scalar| v.as_int::<i64>()),
+      DataType::UInt8 => append_primitive!(UInt8Builder, |v: &Scalar| v.as_int::<u8>()),
+      DataType::UInt16 => append_primitive!(UInt16Builder, |v: &Scalar| v.as_int::<u16>()),
+      DataType::UInt32 => append_primitive!(UInt32Builder, |v: &Scalar| v.as_int::<u32>()),
+      DataType::UInt64 => append_primitive!(UInt64Builder, |v: &Scalar| v.as_int::<u64>()),
+      DataType::Float32 => {
+        append_primitive!(Float32Builder, |v: &Scalar| v.as_f64().map(|f| f as f32))
+      }
+      DataType::Float64 => append_primitive!(Float64Builder, Scalar::as_f64),
+      DataType::Utf8 => {
+        let builder = builder.downcast_mut::<StringBuilder>().unwrap();
+        match value {
+          Scalar::Null => builder.append_null()?,
+          Scalar::Str(s) => builder.append_value(s.as_str())?,
+          v => return Err(mismatch(&v)),
+        }
+      }
+      DataType::List(item) if item.data_type() == &DataType::Utf8 => {
+        let builder = builder
+          .downcast_mut::<ListBuilder<StringBuilder>>()
+          .unwrap();
+        match value {
+          Scalar::Null => builder.append(false)?,
+          Scalar::List(values) => {
+            // the items were checked with the rest of the row
+            for value in values {
+              match value {
+                Scalar::Str(s) => builder.values().append_value(s.as_str())?,
+                _ => builder.values().append_null()?,
+              }
+            }
+            builder.append(true)?
+          }
+          v => return Err(mismatch(&v)),
+        }
+      }
+      DataType::Timestamp(TimeUnit::Second, _) => {
+        append_primitive!(TimestampSecondBuilder, |v: &Scalar| v
+          .as_timestamp(&TimeUnit::Second))
+      }
+      DataType::Timestamp(TimeUnit::Millisecond, _) => {
+        append_primitive!(TimestampMillisecondBuilder, |v: &Scalar| v
+          .as_timestamp(&TimeUnit::Millisecond))
+      }
+      DataType::Timestamp(TimeUnit::Microsecond, _) => {
+        append_primitive!(TimestampMicrosecondBuilder, |v: &Scalar| v
+          .as_timestamp(&TimeUnit::Microsecond))
+      }
+      DataType::Timestamp(TimeUnit::Nanosecond, _) => {
+        append_primitive!(TimestampNanosecondBuilder, |v: &Scalar| v
+          .as_timestamp(&TimeUnit::Nanosecond))
+      }
+      t => return Err(anyhow!("unsupported data type {:?}", t)),
+    }
+
+    Ok(())
+  }
+
+  fn finish(mut self) -> Result<RecordBatch> {
+    let columns = self.builders.iter_mut().map(|b| b.finish()).collect();
+    Ok(RecordBatch::try_new(self.schema, columns)?)
+  }
+}
+
+fn check(field: &Field, value: &Scalar) -> Result<()> {
+  let valid = match (field.data_type(), value) {
+    (_, Scalar::Null) => {
+      if !field.is_nullable() {
+        return Err(anyhow!("column {} is not nullable", field.name()));
+      }
+      true
+    }
+    (DataType::Boolean, v) => v.as_bool().is_some(),
+    (DataType::Int8, v) => v.as_int::<i8>().is_some(),
+    (DataType::Int16, v) => v.as_int::<i16>().is_some(),
+    (DataType::Int32, v) => v.as_int::<i32>().is_some(),
+    (DataType::Int64, v) => v.as_int::<i64>().is_some(),
+    (DataType::UInt8, v) => v.as_int::<u8>().is_some(),
+    (DataType::UInt16, v) => v.as_int::<u16>().is_some(),
+    (DataType::UInt32, v) => v.as_int::<u32>().is_some(),
+    (DataType::UInt64, v) => v.as_int::<u64>().is_some(),
+    (DataType::Float32, v) | (DataType::Float64, v) => v.as_f64().is_some(),
+    (DataType::Utf8, Scalar::Str(_)) => true,
+    (DataType::List(item), Scalar::List(values)) => values.iter().all(|v| check(item, v).is_ok()),
+    (DataType::Timestamp(unit, _), v) => v.as_timestamp(unit).is_some(),
+    _ => false,
+  };
+
+  match valid {
+    true => Ok(()),
+    false => Err(anyhow!(
+      "unable to store {:?} in column {} of type {:?}",
+      value,
+      field.name(),
+      field.data_type()
+    )),
+  }
+}
+
+fn new_builder(data_type: &DataType, capacity: usize) -> Option<Box<dyn ArrayBuilder>> {
+  Some(match data_type {
+    DataType::Boolean => Box::new(BooleanBuilder::new(capacity)),
+    DataType::Int8 => Box::new(Int8Builder::new(capacity)),
+    DataType::Int16 => Box::new(Int16Builder::new(capacity)),
+    DataType::Int32 => Box::new(Int32Builder::new(capacity)),
+    DataType::Int64 => Box::new(Int64Builder::new(capacity)),
+    DataType::UInt8 => Box::new(UInt8Builder::new(capacity)),
+    DataType::UInt16 => Box::new(UInt16Builder::new(capacity)),
+    DataType::UInt32 => Box::new(UInt32Builder::new(capacity)),
+    DataType::UInt64 => Box::new(UInt64Builder::new(capacity)),
+    DataType::Float32 => Box::new(Float32Builder::new(capacity)),
+    DataType::Float64 => Box::new(Float64Builder::new(capacity)),
+    DataType::Utf8 => Box::new(StringBuilder::new(capacity)),
+    DataType::List(item) if item.data_type() == &DataType::Utf8 => {
+      Box::new(ListBuilder::new(StringBuilder::new(capacity)))
+    }
+    DataType::Timestamp(TimeUnit::Second, None) => Box::new(TimestampSecondBuilder::new(capacity)),
+    DataType::Timestamp(TimeUnit::Millisecond, None) => {
+      Box::new(TimestampMillisecondBuilder::new(capacity))
+    }
+    DataType::Timestamp(TimeUnit::Microsecond, None) => {
+      Box::new(TimestampMicrosecondBuilder::new(capacity))
+    }
+    DataType::Timestamp(TimeUnit::Nanosecond, None) => {
+      Box::new(TimestampNanosecondBuilder::new(capacity))
+    }
+    _ => return None,
+  })
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Scalar {
+  Null,
+  Bool(bool),
+  Int(i64),
+  UInt(u64),
+  Float(f64),
+  Str(String),
+  List(Vec<Scalar>),
+}
+
+impl Scalar {
+  fn as_bool(&self) -> Option<bool> {
+    match self {
+      Scalar::Bool(b) => Some(*b),
+      _ => None,
+    }
+  }
+
+  fn as_int<T>(&self) -> Option<T>
+  where
+    T: TryFrom<i64> + TryFrom<u64>,
+  {
+    match self {
+      Scalar::Int(i) => <T as TryFrom<i64>>::try_from(*i).ok(),
+      Scalar::UInt(u) => <T as TryFrom<u64>>::try_from(*u).ok(),
+      _ => None,
+    }
+  }
+
+  fn as_f64(&self) -> Option<f64> {
+    match self {
+      Scalar::Int(i) => Some(*i as f64),
+      Scalar::UInt(u) => Some(*u as f64),
+      Scalar::Float(f) => Some(*f),
+      _ => None,
+    }
+  }
+
+  // integers are taken to already be in the column's unit, strings are parsed as RFC 3339 or as
+  // the naive times and dates chrono serializes, which are taken to be UTC
+  fn as_timestamp(&self, unit: &TimeUnit) -> Option<i64> {
+    match self {
+      Scalar::Str(s) => {
+        let d = match DateTime::parse_from_rfc3339(s.as_str()) {
+          Ok(d) => d.naive_utc(),
+          Err(_) => s
+            .parse::<NaiveDateTime>()
+            .or_else(|_| s.parse::<NaiveDate>().map(|d| d.and_hms(0, 0, 0)))
+            .ok()?,
+        };
+        let per_second = match unit {
+          TimeUnit::Second => 1,
+          TimeUnit::Millisecond => 1_000,
+          TimeUnit::Microsecond => 1_000_000,
+          TimeUnit::Nanosecond => 1_000_000_000,
+        };
+        // nanoseconds only reach from 1677 to 2262, other dates are invalid values
+        let fraction = i64::from(d.timestamp_subsec_nanos()) / (1_000_000_000 / per_second);
+        d.timestamp().checked_mul(per_second)?.checked_add(fraction)
+      }
+      v => v.as_int::<i64>(),
+    }
+  }
+}
+
+#[derive(Debug)]
+struct SerializeError(String);
+
+impl Display for SerializeError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str(self.0.as_str())
+  }
+}
+
+impl std::error::Error for SerializeError {}
+
+impl ser::Error for SerializeError {
+  fn custom<T: Display>(msg: T) -> Self {
+    SerializeError(msg.to_string())
+  }
+}
+
+fn unsupported<T>(kind: &str) -> Result<T, SerializeError> {
+  Err(SerializeError(format!(
+    "unable to encode {} as a column",
+    kind
+  )))
+}
+
+fn unsupported_row<T>() -> Result<T, SerializeError> {
+  Err(SerializeError(
+    "rows must serialize as a struct or map".to_owned(),
+  ))
+}
+
+/// Serializes the fields of a single row into the builders of a `RowEncoder`.
+struct RowSerializer<'a> {
+  encoder: &'a mut RowEncoder,
+  key: Option<String>,
+}
+
+impl<'a> ser::Serializer for RowSerializer<'a> {
+  type Ok = ();
+  type Error = SerializeError;
+  type SerializeSeq = Impossible<(), SerializeError>;
+  type SerializeTuple = Impossible<(), SerializeError>;
+  type SerializeTupleStruct = Impossible<(), SerializeError>;
+  type SerializeTupleVariant = Impossible<(), SerializeError>;
+  type SerializeMap = Self;
+  type SerializeStruct = Self;
+  type SerializeStructVariant = Impossible<(), SerializeError>;
+
+  fn serialize_bool(self, _: bool) -> Result<(), SerializeError> {
+    unsupported_row()
+  }
+  fn serialize_i8(self, _: i8) -> Result<(), SerializeError> {
+    unsupported_row()
+  }
+  fn serialize_i16(self, _: i16) -> Result<(), SerializeError> {
+    unsupported_row()
+  }
+  fn serialize_i32(self, _: i32) -> Result<(), SerializeError> {
+    unsupported_row()
+  }
+  fn serialize_i64(self, _: i64) -> Result<(), SerializeError> {
+    unsupported_row()
+  }
+  fn serialize_u8(self, _: u8) -> Result<(), SerializeError> {
+    unsupported_row()
+  }
+  fn serialize_u16(self, _: u16) -> Result<(), SerializeError> {
+    unsupported_row()
+  }
+  fn serialize_u32(self, _: u32) -> Result<(), SerializeError> {
+    unsupported_row()
+  }
+  fn serialize_u64(self, _: u64) -> Result<(), SerializeError> {
+    unsupported_row()
+  }
+  fn serialize_f32(self, _: f32) -> Result<(), SerializeError> {
+    unsupported_row()
+  }
+  fn serialize_f64(self, _: f64) -> Result<(), SerializeError> {
+    unsupported_row()
+  }
+  fn serialize_char(self, _: char) -> Result<(), SerializeError> {
+    unsupported_row()
+  }
+  fn serialize_str(self, _: &str) -> Result<(), SerializeError> {
+    unsupported_row()
+  }
+  fn serialize_bytes(self, _: &[u8]) -> Result<(), SerializeError> {
+    unsupported_row()
+  }
+  fn serialize_none(self) -> Result<(), SerializeError> {
+    unsupported_row()
+  }
+  fn serialize_some<T: ?Sized + Serialize>(self, value: &T) -> Result<(), SerializeError> {
+    value.serialize(self)
+  }
+  fn serialize_unit(self) -> Result<(), SerializeError> {
+    unsupported_row()
+  }
+  fn serialize_unit_struct(self, _: &'static str) -> Result<(), SerializeError> {
+    unsupported_row()
+  }
+  fn serialize_unit_variant(
+    self,
+    _: &'static str,
+    _: u32,
+    _: &'static str,
+  ) -> Result<(), SerializeError> {
+    unsupported_row()
+  }
+  fn serialize_newtype_struct<T: ?Sized + Serialize>(
+    self,
+    _: &'static str,
+    value: &T,
+  ) -> Result<(), SerializeError> {
+    value.serialize(self)
+  }
+  fn serialize_newtype_variant<T: ?Sized + Serialize>(
+    self,
+    _: &'static str,
+    _: u32,
+    _: &'static str,
+    _: &T,
+  ) -> Result<(), SerializeError> {
+    unsupported_row()
+  }
+  fn serialize_seq(self, _: Option<usize>) -> Result<Self::SerializeSeq, SerializeError> {
+    unsupported_row()
+  }
+  fn serialize_tuple(self, _: usize) -> Result<Self::SerializeTuple, SerializeError> {
+    unsupported_row()
+  }
+  fn serialize_tuple_struct(
+    self,
+    _: &'static str,
+    _: usize,
+  ) -> Result<Self::SerializeTupleStruct, SerializeError> {
+    unsupported_row()
+  }
+  fn serialize_tuple_variant(
+    self,
+    _: &'static str,
+    _: u32,
+    _: &'static str,
+    _: usize,
+  ) -> Result<Self::SerializeTupleVariant, SerializeError> {
+    unsupported_row()
+  }
+  fn serialize_map(self, _: Option<usize>) -> Result<Self::SerializeMap, SerializeError> {
+    Ok(self)
+  }
+  fn serialize_struct(
+    self,
+    _: &'static str,
+    _: usize,
+  ) -> Result<Self::SerializeStruct, SerializeError> {
+    Ok(self)
+  }
+  fn serialize_struct_variant(
+    self,
+    _: &'static str,
+    _: u32,
+    _: &'static str,
+    _: usize,
+  ) -> Result<Self::SerializeStructVariant, SerializeError> {
+    unsupported_row()
+  }
+}
+
+impl<'a> ser::SerializeStruct for RowSerializer<'a> {
+  type Ok = ();
+  type Error = SerializeError;
+
+  fn serialize_field<T: ?Sized + Serialize>(
+    &mut self,
+    key: &'static str,
+    value: &T,
+  ) -> Result<(), SerializeError> {
+    self.encoder.append_field(key, value)
+  }
+
+  fn end(self) -> Result<(), SerializeError> {
+    Ok(())
+  }
+}
+
+impl<'a> ser::SerializeMap for RowSerializer<'a> {
+  type Ok = ();
+  type Error = SerializeError;
+
+  fn serialize_key<T: ?Sized + Serialize>(&mut self, key: &T) -> Result<(), SerializeError> {
+    match key.serialize(ScalarSerializer)? {
+      Scalar::Str(key) => {
+        self.key = Some(key);
+        Ok(())
+      }
+      _ => Err(SerializeError("row keys must be strings".to_owned())),
+    }
+  }
+
+  fn serialize_value<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), SerializeError> {
+    let key = self
+      .key
+      .take()
+      .ok_or_else(|| SerializeError("row value serialized before its key".to_owned()))?;
+    self.encoder.append_field(key.as_str(), value)
+  }
+
+  fn end(self) -> Result<(), SerializeError> {
+    Ok(())
+  }
+}
+
+/// Serializes a single column value.
+struct ScalarSerializer;
+
+impl ser::Serializer for ScalarSerializer {
+  type Ok = Scalar;
+  type Error = SerializeError;
+  type SerializeSeq = ListSerializer;
+  type SerializeTuple = Impossible<Scalar, SerializeError>;
+  type SerializeTupleStruct = Impossible<Scalar, SerializeError>;
+  type SerializeTupleVariant = Impossible<Scalar, SerializeError>;
+  type SerializeMap = Impossible<Scalar, SerializeError>;
+  type SerializeStruct = Impossible<Scalar, SerializeError>;
+  type SerializeStructVariant = Impossible<Scalar, SerializeError>;
+
+  fn serialize_bool(self, v: bool) -> Result<Scalar, SerializeError> {
+    Ok(Scalar::Bool(v))
+  }
+  fn serialize_i8(self, v: i8) -> Result<Scalar, SerializeError> {
+    Ok(Scalar::Int(v.into()))
+  }
+  fn serialize_i16(self, v: i16) -> Result<Scalar, SerializeError> {
+    Ok(Scalar::Int(v.into()))
+  }
+  fn serialize_i32(self, v: i32) -> Result<Scalar, SerializeError> {
+    Ok(Scalar::Int(v.into()))
+  }
+  fn serialize_i64(self, v: i64) -> Result<Scalar, SerializeError> {
+    Ok(Scalar::Int(v))
+  }
+  fn serialize_u8(self, v: u8) -> Result<Scalar, SerializeError> {
+    Ok(Scalar::UInt(v.into()))
+  }
+  fn serialize_u16(self, v: u16) -> Result<Scalar, SerializeError> {
+    Ok(Scalar::UInt(v.into()))
+  }
+  fn serialize_u32(self, v: u32) -> Result<Scalar, SerializeError> {
+    Ok(Scalar::UInt(v.into()))
+  }
+  fn serialize_u64(self, v: u64) -> Result<Scalar, SerializeError> {
+    Ok(Scalar::UInt(v))
+  }
+  fn serialize_f32(self, v: f32) -> Result<Scalar, SerializeError> {
+    Ok(Scalar::Float(v.into()))
+  }
+  fn serialize_f64(self, v: f64) -> Result<Scalar, SerializeError> {
+    Ok(Scalar::Float(v))
+  }
+  fn serialize_char(self, v: char) -> Result<Scalar, SerializeError> {
+    Ok(Scalar::Str(v.to_string()))
+  }
+  fn serialize_str(self, v: &str) -> Result<Scalar, SerializeError> {
+    Ok(Scalar::Str(v.to_owned()))
+  }
+  fn serialize_bytes(self, _: &[u8]) -> Result<Scalar, SerializeError> {
+    unsupported("bytes")
+  }
+  fn serialize_none(self) -> Result<Scalar, SerializeError> {
+    Ok(Scalar::Null)
+  }
+  fn serialize_some<T: ?Sized + Serialize>(self, value: &T) -> Result<Scalar, SerializeError> {
+    value.serialize(self)
+  }
+  fn serialize_unit(self) -> Result<Scalar, SerializeError> {
+    Ok(Scalar::Null)
+  }
+  fn serialize_unit_struct(self, _: &'static str) -> Result<Scalar, SerializeError> {
+    Ok(Scalar::Null)
+  }
+  fn serialize_unit_variant(
+    self,
+    _: &'static str,
+    _: u32,
+    variant: &'static str,
+  ) -> Result<Scalar, SerializeError> {
+    Ok(Scalar::Str(variant.to_owned()))
+  }
+  fn serialize_newtype_struct<T: ?Sized + Serialize>(
+    self,
+    _: &'static str,
+    value: &T,
+  ) -> Result<Scalar, SerializeError> {
+    value.serialize(self)
+  }
+  fn serialize_newtype_variant<T: ?Sized + Serialize>(
+    self,
+    _: &'static str,
+    _: u32,
+    _: &'static str,
+    _: &T,
+  ) -> Result<Scalar, SerializeError> {
+    unsupported("an enum variant with data")
+  }
+  fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq, SerializeError> {
+    Ok(ListSerializer(Vec::with_capacity(len.unwrap_or_default())))
+  }
+  fn serialize_tuple(self, _: usize) -> Result<Self::SerializeTuple, SerializeError> {
+    unsupported("a tuple")
+  }
+  fn serialize_tuple_struct(
+    self,
+    _: &'static str,
+    _: usize,
+  ) -> Result<Self::SerializeTupleStruct, SerializeError> {
+    unsupported("a tuple struct")
+  }
+  fn serialize_tuple_variant(
+    self,
+    _: &'static str,
+    _: u32,
+    _: &'static str,
+    _: usize,
+  ) -> Result<Self::SerializeTupleVariant, SerializeError> {
+    unsupported("an enum variant with data")
+  }
+  fn serialize_map(self, _: Option<usize>) -> Result<Self::SerializeMap, SerializeError> {
+    unsupported("a map")
+  }
+  fn serialize_struct(
+    self,
+    _: &'static str,
+    _: usize,
+  ) -> Result<Self::SerializeStruct, SerializeError> {
+    unsupported("a struct")
+  }
+  fn serialize_struct_variant(
+    self,
+    _: &'static str,
+    _: u32,
+    _: &'static str,
+    _: usize,
+  ) -> Result<Self::SerializeStructVariant, SerializeError> {
+    unsupported("an enum variant with data")
+  }
+}
+
+/// Serializes the items of a list column value.
+struct ListSerializer(Vec<Scalar>);
+
+impl ser::SerializeSeq for ListSerializer {
+  type Ok = Scalar;
+  type Error = SerializeError;
+
+  fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), SerializeError> {
+    match value.serialize(ScalarSerializer)? {
+      Scalar::List(_) => unsupported("a nested sequence"),
+      value => {
+        self.0.push(value);
+        Ok(())
+      }
+    }
+  }
+
+  fn end(self) -> Result<Scalar, SerializeError> {
+    Ok(Scalar::List(self.0))
+  }
+}
@@ -1,34 +1,76 @@
 use std::any::Any;
-use std::collections::HashMap;
 use std::sync::Arc;
 
-use arrow::datatypes::SchemaRef;
-use arrow::json::ReaderBuilder;
+use arrow::datatypes::{Schema, SchemaRef};
+use arrow::error::{ArrowError, Result as ArrowResult};
+use arrow::record_batch::RecordBatch;
 use async_trait::async_trait;
 use datafusion::error::{DataFusionError, Result};
 use datafusion::logical_plan::Expr;
 use datafusion::physical_plan::{ExecutionPlan, Partitioning, SendableRecordBatchStream};
+use futures::StreamExt;
+use tokio::sync::mpsc;
 
-use crate::datafusion::{DataSource, MemoryStream};
+use crate::datafusion::{
+  pushed_options, ChannelStream, LearnedStatistics, QueryContext, StatisticsCollector,
+  StatisticsKey, StreamingDataSource, COLUMNS_OPTION, LIMIT_OPTION, QUERY_CONTEXT,
+};
 
 #[derive(Debug)]
 pub struct JSONExec<D>
 where
-  D: DataSource,
+  D: StreamingDataSource,
 {
   projection: Option<Vec<usize>>,
+  projected_schema: SchemaRef,
+  batch_size: usize,
+  limit: Option<usize>,
   filters: Vec<Expr>,
+  context: QueryContext,
+  statistics: LearnedStatistics,
   datasource: D,
 }
 
 impl<D> JSONExec<D>
 where
-  D: DataSource,
+  D: StreamingDataSource,
 {
-  pub fn new(datasource: D, filters: Vec<Expr>, projection: &Option<Vec<usize>>) -> Self {
+  pub fn new(
+    datasource: D,
+    filters: Vec<Expr>,
+    projection: &Option<Vec<usize>>,
+    batch_size: usize,
+    limit: Option<usize>,
+    context: QueryContext,
+    statistics: LearnedStatistics,
+  ) -> Self {
+    let schema = datasource.schema();
+
+    let projection: Option<Vec<usize>> = projection.clone().map(|projection| {
+      projection
+        .into_iter()
+        .filter(|i| i < &schema.fields().len())
+        .collect()
+    });
+
+    let projected_schema = match &projection {
+      Some(projection) => Arc::new(Schema::new(
+        projection
+          .iter()
+          .map(|i| schema.field(*i).clone())
+          .collect(),
+      )),
+      None => schema,
+    };
+
     Self {
       filters,
-      projection: projection.clone(),
+      projection,
+      projected_schema,
+      batch_size,
+      limit,
+      context,
+      statistics,
       datasource,
     }
   }
@@ -37,14 +79,14 @@ where
 #[async_trait]
 impl<D> ExecutionPlan for JSONExec<D>
 where
-  D: DataSource,
+  D: StreamingDataSource,
 {
   fn as_any(&self) -> &dyn Any {
     self
   }
 
   fn schema(&self) -> SchemaRef {
-    self.datasource.schema().clone()
+    self.projected_schema.clone()
   }
 
   fn children(&self) -> Vec<Arc<dyn ExecutionPlan>> {
@@ -63,55 +105,124 @@ where
   }
 
   async fn execute(&self, _partition: usize) -> Result<SendableRecordBatchStream> {
-    let options =
-      self
-        .filters
-        .clone()
-        .into_iter()
-        .fold(HashMap::<String, String>::new(), |mut acc, exp| {
-          if let Expr::BinaryExpr { left, right, op: _ } = exp {
-            match (*left, *right) {
-              (Expr::Column(key), Expr::Literal(val)) => {
-                acc.insert(key, val.to_string());
-              }
-              _ => {}
-            }
-          };
-          acc
-        });
-
-    let results = match self.datasource.fetch_results(options).await {
-      Ok(r) => Ok(r),
-      Err(e) => Err(DataFusionError::Execution(e.to_string())),
-    }?;
+    let capabilities = self.datasource.capabilities();
+
+    let mut options = pushed_options(&self.filters);
+
+    // the time column is always read, to apply the time range to
+    if let (Some(projection), true) = (&self.projection, capabilities.projection) {
+      let schema = self.datasource.schema();
+      let mut columns: Vec<String> = projection
+        .iter()
+        .map(|i| schema.field(*i).name().clone())
+        .collect();
+      if let Some(time_column) = &capabilities.time_column {
+        if !columns.contains(time_column) {
+          columns.push(time_column.clone());
+        }
+      }
+      options.insert(COLUMNS_OPTION.to_owned(), columns.join(","));
+    }
 
-    let schema = self.schema();
-    let mut builder = ReaderBuilder::new().with_schema(schema.clone());
+    let time_range = match capabilities.time_column {
+      Some(_) => self.context.time_range,
+      None => None,
+    };
 
-    if let Some(projection) = self.projection.clone() {
-      builder = builder.with_projection(
-        projection
-          .clone()
-          .into_iter()
-          .filter(|i| i < &schema.fields().len())
-          .map(|i| schema.fields().get(i))
-          .filter(|f| f.is_some())
-          .map(|f| f.unwrap().name().clone())
-          .collect(),
-      );
+    // rows outside of the time range are only dropped below, after the source counted them
+    if let (Some(limit), true, None) = (self.limit, capabilities.limit, time_range) {
+      options.insert(LIMIT_OPTION.to_owned(), limit.to_string());
     }
 
-    let mut reader = builder.build(results)?;
-    let mut results = Vec::new();
+    // statistics only describe scans that fetch the same rows
+    let key = StatisticsKey::new(&options, time_range);
 
-    while let Ok(Some(r)) = reader.next() {
-      results.push(r);
-    }
+    // execution may happen outside of the task the query was planned in
+    let context = QueryContext {
+      batch_size: Some(self.batch_size),
+      ..self.context.clone()
+    };
+    let fetch = self.datasource.fetch_stream(options, time_range);
+    let mut results = match QUERY_CONTEXT.scope(context, fetch).await {
+      Ok(r) => Ok(r),
+      Err(e) => Err(DataFusionError::Execution(e.to_string())),
+    }?;
 
-    Ok(Box::pin(MemoryStream::try_new(
-      results.clone(),
-      reader.schema().clone(),
-      None,
-    )?))
+    let schema = self.datasource.schema();
+
+    let time_filter = match (time_range, &capabilities.time_column) {
+      (Some(range), Some(column)) => Some((range, schema.index_of(column.as_str())?)),
+      _ => None,
+    };
+
+    let time_column = capabilities
+      .time_column
+      .and_then(|column| schema.index_of(column.as_str()).ok());
+    let mut collector = StatisticsCollector::new(schema.fields().len(), time_column);
+    let statistics = self.statistics.clone();
+
+    let projection = self.projection.clone();
+    let mut remaining = self.limit;
+    let (sender, receiver) = mpsc::channel::<ArrowResult<RecordBatch>>(2);
+    let output_schema = self.schema();
+
+    tokio::spawn(async move {
+      let mut complete = true;
+
+      while let Some(batch) = results.next().await {
+        let batch = batch
+          .map_err(|e| ArrowError::ExternalError(e.into()))
+          .and_then(|batch| match batch.schema().fields() == schema.fields() {
+            true => Ok(batch),
+            false => Err(ArrowError::SchemaError(format!(
+              "batch schema {:?} does not match the datasource schema {:?}",
+              batch.schema(),
+              schema
+            ))),
+          })
+          .and_then(|batch| match time_filter {
+            Some((range, index)) => range.filter(&batch, index),
+            None => Ok(batch),
+          })
+          .map(|batch| {
+            collector.update(&batch);
+            batch
+          })
+          .and_then(|batch| match &projection {
+            Some(projection) => RecordBatch::try_new(
+              output_schema.clone(),
+              projection
+                .iter()
+                .map(|i| batch.column(*i).clone())
+                .collect(),
+            ),
+            None => Ok(batch),
+          })
+          .and_then(|batch| match remaining {
+            Some(limit) if batch.num_rows() > limit => RecordBatch::try_new(
+              batch.schema(),
+              batch.columns().iter().map(|c| c.slice(0, limit)).collect(),
+            ),
+            _ => Ok(batch),
+          });
+
+        if let (Some(limit), Ok(batch)) = (remaining.as_mut(), &batch) {
+          *limit -= batch.num_rows();
+        }
+
+        let failed = batch.is_err();
+        if sender.send(batch).await.is_err() || failed || remaining == Some(0) {
+          complete = false;
+          break;
+        }
+      }
+
+      // partial fetches would underestimate the table
+      if complete {
+        statistics.learn(key, collector.finish());
+      }
+    });
+
+    Ok(Box::pin(ChannelStream::new(self.schema(), receiver)))
   }
 }
@@ -0,0 +1,94 @@
+use std::collections::HashMap;
+use std::fmt::Debug;
+
+use anyhow::Result;
+use arrow::datatypes::SchemaRef;
+use async_trait::async_trait;
+use futures::stream::{self, StreamExt, TryStreamExt};
+
+use crate::datafusion::{
+  current_query_context, BatchStream, Statistics, StreamingDataSource, TableCapabilities,
+  TimeRange, QUERY_CONTEXT,
+};
+
+const DEFAULT_CONCURRENCY: usize = 4;
+
+/// Splits a query into several queries of a datasource, e.g. one for each repo of an
+/// organization.
+#[async_trait]
+pub trait Partitioner: Send + Sync + Clone + Debug + 'static {
+  /// The options of each query, from the options of the query that is split.
+  async fn partitions(
+    &self,
+    options: &HashMap<String, String>,
+  ) -> Result<Vec<HashMap<String, String>>>;
+}
+
+/// Fetches every partition of a query from a datasource and streams their batches one
+/// partition after the other.
+///
+/// Partitions are fetched a few at a time, with the context of the query they belong to.
+#[derive(Debug, Clone)]
+pub struct FanOut<D, P> {
+  datasource: D,
+  partitioner: P,
+  concurrency: usize,
+}
+
+impl<D, P> FanOut<D, P>
+where
+  D: StreamingDataSource,
+  P: Partitioner,
+{
+  pub fn new(datasource: D, partitioner: P) -> Self {
+    Self::with_concurrency(datasource, partitioner, DEFAULT_CONCURRENCY)
+  }
+
+  /// Fetches at most `concurrency` partitions at once.
+  pub fn with_concurrency(datasource: D, partitioner: P, concurrency: usize) -> Self {
+    Self {
+      datasource,
+      partitioner,
+      concurrency: concurrency.max(1),
+    }
+  }
+}
+
+#[async_trait]
+impl<D, P> StreamingDataSource for FanOut<D, P>
+where
+  D: StreamingDataSource,
+  P: Partitioner,
+{
+  async fn fetch_stream(
+    &self,
+    options: HashMap<String, String>,
+    time_range: Option<TimeRange>,
+  ) -> Result<BatchStream> {
+    let partitions = self.partitioner.partitions(&options).await?;
+    let context = current_query_context();
+    let datasource = self.datasource.clone();
+
+    // the stream is read outside of the query context
+    let streams = stream::iter(partitions).map(move |options| {
+      let datasource = datasource.clone();
+      QUERY_CONTEXT.scope(context.clone(), async move {
+        datasource.fetch_stream(options, time_range).await
+      })
+    });
+
+    Ok(streams.buffered(self.concurrency).try_flatten().boxed())
+  }
+
+  fn schema(&self) -> SchemaRef {
+    self.datasource.schema()
+  }
+
+  fn capabilities(&self) -> TableCapabilities {
+    self.datasource.capabilities()
+  }
+
+  fn statistics(&self) -> Option<Statistics> {
+    self.datasource.statistics()
+  }
+}
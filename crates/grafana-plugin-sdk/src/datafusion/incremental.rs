@@ -0,0 +1,93 @@
+use std::collections::HashSet;
+
+use anyhow::{anyhow, Result};
+use arrow::array::{Array, BooleanArray, Int64Array};
+use arrow::compute::{cast, filter_record_batch};
+use arrow::datatypes::{DataType, TimeUnit};
+use arrow::record_batch::RecordBatch;
+use arrow::util::display::array_value_to_string;
+use chrono::{DateTime, SecondsFormat, TimeZone, Utc};
+
+/// The latest value of the `column` timestamp, formatted for `SINCE_OPTION`.
+pub(crate) fn watermark(batches: &[RecordBatch], column: &str) -> Result<Option<String>> {
+  let mut max: Option<DateTime<Utc>> = None;
+
+  for batch in batches {
+    let index = batch.schema().index_of(column)?;
+    let unit = match batch.schema().field(index).data_type() {
+      DataType::Timestamp(unit, _) => unit.clone(),
+      t => {
+        return Err(anyhow!(
+          "watermark column {} is a {:?}, not a timestamp",
+          column,
+          t
+        ))
+      }
+    };
+
+    let values = cast(batch.column(index), &DataType::Int64)?;
+    let values = values
+      .as_any()
+      .downcast_ref::<Int64Array>()
+      .ok_or_else(|| anyhow!("watermark column {} could not be read", column))?;
+
+    let latest = (0..values.len())
+      .filter(|i| values.is_valid(*i))
+      .map(|i| timestamp(values.value(i), &unit))
+      .max();
+
+    max = max.max(latest);
+  }
+
+  Ok(max.map(|t| t.to_rfc3339_opts(SecondsFormat::Secs, true)))
+}
+
+fn timestamp(value: i64, unit: &TimeUnit) -> DateTime<Utc> {
+  match unit {
+    TimeUnit::Second => Utc.timestamp(value, 0),
+    TimeUnit::Millisecond => Utc.timestamp_millis(value),
+    TimeUnit::Microsecond => Utc.timestamp_nanos(value.saturating_mul(1_000)),
+    TimeUnit::Nanosecond => Utc.timestamp_nanos(value),
+  }
+}
+
+/// Appends `changed` to `cached`, dropping the cached rows that have the key of a changed row.
+pub(crate) fn merge(
+  cached: &[RecordBatch],
+  changed: Vec<RecordBatch>,
+  key: &[String],
+) -> Result<Vec<RecordBatch>> {
+  let batches: Vec<&RecordBatch> = cached.iter().chain(changed.iter()).collect();
+  let mut seen = HashSet::new();
+  let mut keep = vec![];
+
+  // later rows win, so the batches are walked from the end
+  for batch in batches.iter().rev() {
+    let columns = key
+      .iter()
+      .map(|k| Ok(batch.column(batch.schema().index_of(k)?).clone()))
+      .collect::<Result<Vec<_>>>()?;
+
+    let mut mask = vec![false; batch.num_rows()];
+    for row in (0..batch.num_rows()).rev() {
+      let values = columns
+        .iter()
+        .map(|c| array_value_to_string(c, row))
+        .collect::<std::result::Result<Vec<String>, _>>()?;
+      mask[row] = seen.insert(values);
+    }
+    keep.push(BooleanArray::from(mask));
+  }
+  keep.reverse();
+
+  Ok(
+    batches
+      .into_iter()
+      .zip(keep.iter())
+      .map(|(batch, mask)| filter_record_batch(batch, mask))
+      .collect::<std::result::Result<Vec<RecordBatch>, _>>()?
+      .into_iter()
+      .filter(|batch| batch.num_rows() > 0)
+      .collect(),
+  )
+}
@@ -0,0 +1,244 @@
+use std::collections::HashMap;
+use std::fmt::Debug;
+
+use anyhow::Result;
+use arrow::datatypes::SchemaRef;
+use arrow::record_batch::RecordBatch;
+use async_trait::async_trait;
+use futures::stream::{self, StreamExt};
+use serde::Serialize;
+use tokio::sync::mpsc::{self, Sender};
+
+use crate::datafusion::{
+  current_query_context, encode, BatchStream, RateLimiter, Statistics, StreamingDataSource,
+  TableCapabilities, TimeRange, LIMIT_OPTION, QUERY_CONTEXT,
+};
+
+const PAGE_BUFFER: usize = 2;
+
+/// Identifies a page of a paginated API.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PageToken {
+  First,
+  Number(u32),
+  Cursor(String),
+  /// A page URL, e.g. from a `Link: <...>; rel="next"` header.
+  Url(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct Page<R> {
+  pub rows: Vec<R>,
+  /// The page after this one, if there is one.
+  pub next: Option<PageToken>,
+  /// The number of pages, if the API reports it. When `next` is a page number, the remaining
+  /// pages are then fetched concurrently.
+  pub total_pages: Option<u32>,
+}
+
+/// A source that fetches its rows one page at a time.
+///
+/// Wrap it in `Pages` to register it with a `JSONTableProvider`.
+#[async_trait]
+pub trait PagedDataSource: Send + Sync + Clone + Debug + 'static {
+  type Row: Serialize + Send + Sync;
+
+  async fn fetch_page(
+    &self,
+    options: &HashMap<String, String>,
+    time_range: Option<TimeRange>,
+    page: PageToken,
+  ) -> Result<Page<Self::Row>>;
+
+  fn schema(&self) -> SchemaRef;
+
+  fn required_filters(&self) -> Vec<String> {
+    vec![]
+  }
+
+  fn time_column(&self) -> Option<String> {
+    None
+  }
+
+  fn capabilities(&self) -> TableCapabilities {
+    TableCapabilities::from_metadata(&self.schema(), self.required_filters(), self.time_column())
+  }
+
+  fn statistics(&self) -> Option<Statistics> {
+    None
+  }
+
+  /// The most pages fetched at once when the number of pages is known.
+  fn prefetch(&self) -> usize {
+    4
+  }
+}
+
+/// Fetches the pages of a `PagedDataSource` and streams their rows as they arrive.
+///
+/// Paging stops early once the query limit is reached.
+#[derive(Debug, Clone)]
+pub struct Pages<D> {
+  datasource: D,
+  limiter: Option<RateLimiter>,
+}
+
+impl<D> Pages<D>
+where
+  D: PagedDataSource,
+{
+  pub fn new(datasource: D) -> Self {
+    Self {
+      datasource,
+      limiter: None,
+    }
+  }
+
+  /// Retries and throttles every page fetch with `limiter`.
+  pub fn with_limiter(datasource: D, limiter: RateLimiter) -> Self {
+    Self {
+      datasource,
+      limiter: Some(limiter),
+    }
+  }
+
+  async fn fetch_page(
+    &self,
+    options: &HashMap<String, String>,
+    time_range: Option<TimeRange>,
+    page: PageToken,
+  ) -> Result<Page<D::Row>> {
+    match &self.limiter {
+      Some(limiter) => {
+        limiter
+          .run(|| {
+            self
+              .datasource
+              .fetch_page(options, time_range, page.clone())
+          })
+          .await
+      }
+      None => self.datasource.fetch_page(options, time_range, page).await,
+    }
+  }
+
+  /// Sends the rows of every page until the last one, the limit or a dropped receiver.
+  async fn send_pages(
+    &self,
+    options: HashMap<String, String>,
+    time_range: Option<TimeRange>,
+    sender: &Sender<Result<RecordBatch>>,
+  ) -> Result<()> {
+    let mut rows = Sent {
+      schema: self.datasource.schema(),
+      count: 0,
+      limit: options.get(LIMIT_OPTION).and_then(|l| l.parse().ok()),
+    };
+
+    let first = self
+      .fetch_page(&options, time_range, PageToken::First)
+      .await?;
+    let (next, total_pages) = (first.next, first.total_pages);
+    if !rows.send(first.rows, sender).await? {
+      return Ok(());
+    }
+
+    match (next, total_pages) {
+      (Some(PageToken::Number(start)), Some(total_pages)) => {
+        let mut pages = stream::iter(start..=total_pages)
+          .map(|n| self.fetch_page(&options, time_range, PageToken::Number(n)))
+          .buffered(self.datasource.prefetch().max(1));
+
+        while let Some(page) = pages.next().await {
+          if !rows.send(page?.rows, sender).await? {
+            break;
+          }
+        }
+      }
+      (mut next, _) => {
+        while let Some(token) = next {
+          let page = self.fetch_page(&options, time_range, token).await?;
+          next = page.next;
+          if !rows.send(page.rows, sender).await? {
+            break;
+          }
+        }
+      }
+    }
+
+    Ok(())
+  }
+}
+
+/// Counts the rows sent for a fetch.
+struct Sent {
+  schema: SchemaRef,
+  count: usize,
+  limit: Option<usize>,
+}
+
+impl Sent {
+  /// Encodes and sends rows up to the limit, returning whether more rows are wanted.
+  async fn send<R: Serialize>(
+    &mut self,
+    rows: Vec<R>,
+    sender: &Sender<Result<RecordBatch>>,
+  ) -> Result<bool> {
+    let wanted = match self.limit {
+      Some(limit) => limit.saturating_sub(self.count).min(rows.len()),
+      None => rows.len(),
+    };
+    self.count += wanted;
+
+    for batch in encode(self.schema.clone(), &rows[..wanted])? {
+      if sender.send(Ok(batch)).await.is_err() {
+        return Ok(false);
+      }
+    }
+
+    Ok(self.limit.map_or(true, |limit| self.count < limit))
+  }
+}
+
+#[async_trait]
+impl<D> StreamingDataSource for Pages<D>
+where
+  D: PagedDataSource,
+{
+  async fn fetch_stream(
+    &self,
+    options: HashMap<String, String>,
+    time_range: Option<TimeRange>,
+  ) -> Result<BatchStream> {
+    let (sender, receiver) = mpsc::channel(PAGE_BUFFER);
+    let pages = self.clone();
+
+    tokio::spawn(QUERY_CONTEXT.scope(current_query_context(), async move {
+      if let Err(e) = pages.send_pages(options, time_range, &sender).await {
+        sender.send(Err(e)).await.ok();
+      }
+    }));
+
+    Ok(
+      stream::unfold(receiver, |mut receiver| async move {
+        receiver.recv().await.map(|batch| (batch, receiver))
+      })
+      .boxed(),
+    )
+  }
+
+  fn schema(&self) -> SchemaRef {
+    self.datasource.schema()
+  }
+
+  fn capabilities(&self) -> TableCapabilities {
+    TableCapabilities {
+      limit: true,
+      ..self.datasource.capabilities()
+    }
+  }
+
+  fn statistics(&self) -> Option<Statistics> {
+    self.datasource.statistics()
+  }
+}
@@ -0,0 +1,359 @@
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::future::Future;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, Result};
+use arrow::datatypes::SchemaRef;
+use async_trait::async_trait;
+use serde::Serialize;
+use tokio::sync::Semaphore;
+
+use crate::datafusion::{
+  BatchStream, Statistics, StreamingDataSource, TableCapabilities, TimeRange,
+};
+use crate::plugin::Reporter;
+
+/// An upstream failure that is worth retrying, e.g. a rate limit or a 5xx response.
+///
+/// Datasources return it from their fetches for `RetryDataSource` to retry them, any other
+/// error fails the query straight away.
+#[derive(Debug, Clone)]
+pub struct RetryableError {
+  pub message: String,
+  /// How long the upstream asked to wait, from a `Retry-After` or rate limit reset header.
+  pub retry_after: Option<Duration>,
+  /// The upstream rate limit is exhausted, so every fetch sharing the limiter waits.
+  pub rate_limited: bool,
+}
+
+impl RetryableError {
+  pub fn rate_limited(message: impl Into<String>, retry_after: Option<Duration>) -> Self {
+    Self {
+      message: message.into(),
+      retry_after,
+      rate_limited: true,
+    }
+  }
+
+  pub fn unavailable(message: impl Into<String>, retry_after: Option<Duration>) -> Self {
+    Self {
+      message: message.into(),
+      retry_after,
+      rate_limited: false,
+    }
+  }
+}
+
+impl fmt::Display for RetryableError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str(&self.message)
+  }
+}
+
+impl Error for RetryableError {}
+
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+  pub max_retries: u32,
+  /// The delay before the first retry, which doubles with every further one.
+  pub initial_backoff: Duration,
+  pub max_backoff: Duration,
+  /// The most fetches that run at once.
+  pub max_concurrency: usize,
+  /// The average number of fetches started per second, if they are limited.
+  pub requests_per_second: Option<f64>,
+  /// The number of fetches that can be started at once before `requests_per_second` applies.
+  pub burst: u32,
+}
+
+impl Default for RetryPolicy {
+  fn default() -> Self {
+    Self {
+      max_retries: 3,
+      initial_backoff: Duration::from_millis(500),
+      max_backoff: Duration::from_secs(60),
+      max_concurrency: 4,
+      requests_per_second: None,
+      burst: 10,
+    }
+  }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RateLimitStats {
+  pub attempts: u64,
+  pub retries: u64,
+  /// Attempts that failed because the upstream rate limit was exhausted.
+  pub rate_limited: u64,
+  /// Calls that failed after all their retries, or with an error that isn't retried.
+  pub failures: u64,
+  pub in_flight: usize,
+  /// How long fetches still wait for the upstream rate limit to reset.
+  pub throttled_ms: u64,
+}
+
+#[derive(Debug)]
+struct LimiterState {
+  tokens: f64,
+  refilled_at: Instant,
+  throttled_until: Option<Instant>,
+  stats: RateLimitStats,
+}
+
+/// Retries, throttles and limits the concurrency of calls to an upstream API.
+///
+/// Clones share their limits, so one limiter can be used by every table of a datasource.
+#[derive(Debug, Clone)]
+pub struct RateLimiter {
+  policy: RetryPolicy,
+  semaphore: Arc<Semaphore>,
+  state: Arc<Mutex<LimiterState>>,
+}
+
+impl RateLimiter {
+  pub fn new(policy: RetryPolicy) -> Self {
+    Self {
+      semaphore: Arc::new(Semaphore::new(policy.max_concurrency.max(1))),
+      state: Arc::new(Mutex::new(LimiterState {
+        tokens: f64::from(policy.burst),
+        refilled_at: Instant::now(),
+        throttled_until: None,
+        stats: RateLimitStats::default(),
+      })),
+      policy,
+    }
+  }
+
+  pub fn stats(&self) -> RateLimitStats {
+    let state = match self.state.lock() {
+      Ok(s) => s,
+      Err(_) => return RateLimitStats::default(),
+    };
+    let throttled = state
+      .throttled_until
+      .map(|t| t.saturating_duration_since(Instant::now()))
+      .unwrap_or_default();
+
+    RateLimitStats {
+      throttled_ms: throttled.as_millis() as u64,
+      ..state.stats.clone()
+    }
+  }
+
+  /// Calls `f` until it succeeds, fails with an error that isn't a `RetryableError`, or runs
+  /// out of retries.
+  ///
+  /// Calls fail straight away instead of waiting longer than `max_backoff` for a rate limit to
+  /// reset.
+  pub async fn run<T, F, Fut>(&self, mut f: F) -> Result<T>
+  where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T>>,
+  {
+    let mut retries = 0;
+
+    loop {
+      let result = {
+        let _permit = self
+          .semaphore
+          .acquire()
+          .await
+          .map_err(|_| anyhow!("rate limiter closed"))?;
+        self.wait_for_token().await?;
+
+        let _attempt = Attempt::start(self);
+        f().await
+      };
+
+      let error = match result {
+        Ok(value) => return Ok(value),
+        Err(e) => e,
+      };
+
+      let retryable = error
+        .chain()
+        .find_map(|e| e.downcast_ref::<RetryableError>())
+        .cloned();
+      let retryable = match retryable {
+        Some(r) => r,
+        None => {
+          self.update(|s| s.stats.failures += 1);
+          return Err(error);
+        }
+      };
+
+      let delay = retryable
+        .retry_after
+        .unwrap_or_else(|| self.backoff(retries));
+      let retry = retries < self.policy.max_retries && delay <= self.policy.max_backoff;
+      retries += 1;
+
+      self.update(|s| {
+        if retryable.rate_limited {
+          s.stats.rate_limited += 1;
+          let until = Instant::now() + delay;
+          s.throttled_until = s.throttled_until.max(Some(until));
+        }
+        match retry {
+          true => s.stats.retries += 1,
+          false => s.stats.failures += 1,
+        }
+      });
+
+      if !retry {
+        return Err(error);
+      }
+
+      if !retryable.rate_limited {
+        tokio::time::sleep(delay).await;
+      }
+    }
+  }
+
+  fn update(&self, f: impl FnOnce(&mut LimiterState)) {
+    if let Ok(mut state) = self.state.lock() {
+      f(&mut state);
+    }
+  }
+
+  /// Exponential backoff with jitter, so retries of concurrent calls are spread out.
+  fn backoff(&self, retries: u32) -> Duration {
+    let backoff = self
+      .policy
+      .initial_backoff
+      .checked_mul(2u32.saturating_pow(retries))
+      .unwrap_or(self.policy.max_backoff)
+      .min(self.policy.max_backoff);
+
+    let jitter = RandomState::new().build_hasher().finish() % 1000;
+    backoff / 2 + backoff / 2 * jitter as u32 / 1000
+  }
+
+  /// Waits until the upstream rate limit resets and a token of the bucket is available.
+  async fn wait_for_token(&self) -> Result<()> {
+    loop {
+      let wait = {
+        let mut state = self
+          .state
+          .lock()
+          .map_err(|_| anyhow!("rate limiter lock poisoned"))?;
+        let now = Instant::now();
+
+        match state.throttled_until {
+          Some(until) if until > now + self.policy.max_backoff => {
+            let wait = until - now;
+            return Err(
+              RetryableError::rate_limited(
+                format!("rate limited for another {}s", wait.as_secs()),
+                Some(wait),
+              )
+              .into(),
+            );
+          }
+          Some(until) if until > now => until - now,
+          _ => match self.policy.requests_per_second {
+            None => return Ok(()),
+            Some(rate) => {
+              let elapsed = now.duration_since(state.refilled_at).as_secs_f64();
+              state.tokens = (state.tokens + elapsed * rate).min(f64::from(self.policy.burst));
+              state.refilled_at = now;
+
+              if state.tokens >= 1.0 {
+                state.tokens -= 1.0;
+                return Ok(());
+              }
+              Duration::from_secs_f64((1.0 - state.tokens) / rate)
+            }
+          },
+        }
+      };
+
+      tokio::time::sleep(wait).await;
+    }
+  }
+}
+
+/// Counts an attempt as in flight until it is dropped, even if the call is cancelled.
+struct Attempt<'a>(&'a RateLimiter);
+
+impl<'a> Attempt<'a> {
+  fn start(limiter: &'a RateLimiter) -> Self {
+    limiter.update(|s| {
+      s.stats.attempts += 1;
+      s.stats.in_flight += 1;
+    });
+    Self(limiter)
+  }
+}
+
+impl Drop for Attempt<'_> {
+  fn drop(&mut self) {
+    self.0.update(|s| s.stats.in_flight -= 1);
+  }
+}
+
+impl Reporter for RateLimiter {
+  fn kind(&self) -> &'static str {
+    "rate_limit"
+  }
+
+  fn report(&self) -> serde_json::Value {
+    serde_json::to_value(self.stats()).unwrap_or_default()
+  }
+}
+
+/// Retries the fetches of a datasource that fail with a `RetryableError`.
+///
+/// Only `fetch_stream` is retried, errors in the returned stream fail the query.
+#[derive(Debug, Clone)]
+pub struct RetryDataSource<D> {
+  datasource: D,
+  limiter: RateLimiter,
+}
+
+impl<D> RetryDataSource<D>
+where
+  D: StreamingDataSource,
+{
+  pub fn new(datasource: D, limiter: RateLimiter) -> Self {
+    Self {
+      datasource,
+      limiter,
+    }
+  }
+}
+
+#[async_trait]
+impl<D> StreamingDataSource for RetryDataSource<D>
+where
+  D: StreamingDataSource,
+{
+  async fn fetch_stream(
+    &self,
+    options: HashMap<String, String>,
+    time_range: Option<TimeRange>,
+  ) -> Result<BatchStream> {
+    self
+      .limiter
+      .run(|| self.datasource.fetch_stream(options.clone(), time_range))
+      .await
+  }
+
+  fn schema(&self) -> SchemaRef {
+    self.datasource.schema()
+  }
+
+  fn capabilities(&self) -> TableCapabilities {
+    self.datasource.capabilities()
+  }
+
+  fn statistics(&self) -> Option<Statistics> {
+    self.datasource.statistics()
+  }
+}
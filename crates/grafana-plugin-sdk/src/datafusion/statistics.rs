@@ -0,0 +1,162 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, RwLock};
+
+use arrow::array::{Array, Int64Array};
+use arrow::compute::cast;
+use arrow::datatypes::DataType;
+use arrow::record_batch::RecordBatch;
+use datafusion::datasource::datasource::{ColumnStatistics, Statistics};
+use datafusion::scalar::ScalarValue;
+
+use crate::datafusion::{TimeRange, COLUMNS_OPTION};
+
+/// The number of option sets statistics are kept for, as time ranges change with every query.
+const MAX_LEARNED: usize = 64;
+
+/// The pushed down options and time range of a fetch, which only match the rows of another
+/// fetch with the same ones. The columns a fetch reads don't change its rows, so they're left
+/// out.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub(crate) struct StatisticsKey {
+  options: BTreeMap<String, String>,
+  time_range: Option<TimeRange>,
+}
+
+impl StatisticsKey {
+  pub fn new(options: &HashMap<String, String>, time_range: Option<TimeRange>) -> Self {
+    let options = options
+      .iter()
+      .filter(|(key, _)| key.as_str() != COLUMNS_OPTION)
+      .map(|(key, value)| (key.clone(), value.clone()))
+      .collect();
+    Self {
+      options,
+      time_range,
+    }
+  }
+}
+
+/// The statistics of the most recent complete fetch with each set of options, shared between a
+/// table and its scans.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct LearnedStatistics(Arc<RwLock<HashMap<StatisticsKey, Statistics>>>);
+
+impl LearnedStatistics {
+  pub fn get(&self, key: &StatisticsKey) -> Option<Statistics> {
+    self.0.read().ok()?.get(key).cloned()
+  }
+
+  pub fn learn(&self, key: StatisticsKey, statistics: Statistics) {
+    let mut learned = match self.0.write() {
+      Ok(l) => l,
+      Err(_) => return,
+    };
+
+    if learned.len() >= MAX_LEARNED && !learned.contains_key(&key) {
+      if let Some(evicted) = learned.keys().next().cloned() {
+        learned.remove(&evicted);
+      }
+    }
+    learned.insert(key, statistics);
+  }
+}
+
+/// Accumulates row counts, null counts and time column bounds over the batches of a fetch.
+#[derive(Debug)]
+pub(crate) struct StatisticsCollector {
+  time_column: Option<usize>,
+  num_rows: usize,
+  total_byte_size: usize,
+  null_counts: Vec<usize>,
+  min: Option<(i64, ScalarValue)>,
+  max: Option<(i64, ScalarValue)>,
+}
+
+impl StatisticsCollector {
+  pub fn new(columns: usize, time_column: Option<usize>) -> Self {
+    Self {
+      null_counts: vec![0; columns],
+      time_column,
+      num_rows: 0,
+      total_byte_size: 0,
+      min: None,
+      max: None,
+    }
+  }
+
+  pub fn update(&mut self, batch: &RecordBatch) {
+    self.num_rows += batch.num_rows();
+
+    for (i, column) in batch.columns().iter().enumerate() {
+      self.total_byte_size += column.get_array_memory_size();
+      if let Some(count) = self.null_counts.get_mut(i) {
+        *count += column.null_count();
+      }
+    }
+
+    if let Some(index) = self.time_column {
+      self.update_bounds(batch, index);
+    }
+  }
+
+  fn update_bounds(&mut self, batch: &RecordBatch, index: usize) {
+    let column = batch.column(index);
+    let values = match cast(column, &DataType::Int64) {
+      Ok(v) => v,
+      Err(_) => return,
+    };
+    let values = match values.as_any().downcast_ref::<Int64Array>() {
+      Some(v) => v,
+      None => return,
+    };
+
+    for i in 0..values.len() {
+      if values.is_null(i) {
+        continue;
+      }
+
+      let value = values.value(i);
+      if self.min.as_ref().map_or(true, |(min, _)| value < *min) {
+        if let Ok(scalar) = ScalarValue::try_from_array(column, i) {
+          self.min = Some((value, scalar));
+        }
+      }
+      if self.max.as_ref().map_or(true, |(max, _)| value > *max) {
+        if let Ok(scalar) = ScalarValue::try_from_array(column, i) {
+          self.max = Some((value, scalar));
+        }
+      }
+    }
+  }
+
+  pub fn finish(self) -> Statistics {
+    let time_column = self.time_column;
+    let (min, max) = (self.min, self.max);
+
+    let column_statistics = self
+      .null_counts
+      .iter()
+      .enumerate()
+      .map(|(i, null_count)| {
+        let is_time_column = time_column == Some(i);
+        ColumnStatistics {
+          null_count: Some(*null_count),
+          min_value: min
+            .as_ref()
+            .filter(|_| is_time_column)
+            .map(|(_, v)| v.clone()),
+          max_value: max
+            .as_ref()
+            .filter(|_| is_time_column)
+            .map(|(_, v)| v.clone()),
+        }
+      })
+      .collect();
+
+    Statistics {
+      num_rows: Some(self.num_rows),
+      total_byte_size: Some(self.total_byte_size),
+      column_statistics: Some(column_statistics),
+    }
+  }
+}
@@ -5,52 +5,72 @@ use arrow::datatypes::SchemaRef;
 use datafusion::datasource::datasource::Statistics;
 use datafusion::datasource::datasource::TableProviderFilterPushDown;
 use datafusion::datasource::TableProvider;
-use datafusion::error::Result;
-use datafusion::logical_plan::{Expr, Operator};
+use datafusion::error::{DataFusionError, Result};
+use datafusion::logical_plan::Expr;
 use datafusion::physical_plan::ExecutionPlan;
 
-use crate::datafusion::{DataSource, JSONExec};
+use crate::datafusion::{
+  current_query_context, pushed_filter, pushed_options, FilterOperator, JSONExec,
+  LearnedStatistics, StatisticsKey, StreamingDataSource, TableCapabilities,
+};
 
 #[derive(Debug)]
 pub struct JSONTableProvider<D>
 where
-  D: DataSource,
+  D: StreamingDataSource,
 {
   datasource: D,
-  statistics: Statistics,
+  statistics: LearnedStatistics,
 }
 
 impl<D> JSONTableProvider<D>
 where
-  D: DataSource,
+  D: StreamingDataSource,
 {
   pub fn new(datasource: D) -> Self {
     Self {
       datasource,
-      statistics: Statistics::default(),
+      statistics: LearnedStatistics::default(),
     }
   }
 
-  fn supports_filtering(&self, name: String) -> bool {
-    let schema = self.datasource.schema();
+  /// What the datasource supports, for tooling that inspects registered tables.
+  pub fn capabilities(&self) -> TableCapabilities {
+    self.datasource.capabilities()
+  }
 
-    let field = schema.field_with_name(name.as_str());
-    if field.is_err() {
-      return false;
-    }
+  /// The statistics of the most recent complete scan with the same pushed down filters and
+  /// time range as a scan with `filters` in the current query, e.g. of a single repository of
+  /// a table that requires one.
+  pub fn scan_statistics(&self, filters: &[Expr]) -> Statistics {
+    let time_range = match self.capabilities().time_column {
+      Some(_) => current_query_context().time_range,
+      None => None,
+    };
+    let key = StatisticsKey::new(&pushed_options(filters), time_range);
+    self.statistics.get(&key).unwrap_or_default()
+  }
 
-    let metadata = field.unwrap().metadata().clone();
-    if metadata.is_none() {
-      return false;
-    }
+  fn missing_filters(&self, filters: &[Expr]) -> Vec<String> {
+    let provided: Vec<&String> = filters
+      .iter()
+      .filter_map(pushed_filter)
+      .filter(|(_, op, _)| *op == FilterOperator::Eq)
+      .map(|(name, _, _)| name)
+      .collect();
 
-    metadata.unwrap().contains_key("filter")
+    self
+      .capabilities()
+      .required_filters()
+      .into_iter()
+      .filter(|name| !provided.contains(&name))
+      .collect()
   }
 }
 
 impl<D> TableProvider for JSONTableProvider<D>
 where
-  D: DataSource,
+  D: StreamingDataSource,
 {
   fn as_any(&self) -> &dyn Any {
     self
@@ -60,42 +80,54 @@ where
     self.datasource.schema().clone()
   }
 
-  // TODO: clean this up
   fn supports_filter_pushdown(&self, filter: &Expr) -> Result<TableProviderFilterPushDown> {
-    if let Expr::BinaryExpr { left, right, op } = filter {
-      if let Expr::Column(name) = &**left {
-        if self.supports_filtering(name.clone()) {
-          if matches!(op, Operator::Eq) && matches!(**right, Expr::Literal(..)) {
-            Ok(TableProviderFilterPushDown::Exact)
-          } else {
-            Ok(TableProviderFilterPushDown::Unsupported)
-          }
-        } else {
-          Ok(TableProviderFilterPushDown::Unsupported)
-        }
-      } else {
-        Ok(TableProviderFilterPushDown::Unsupported)
-      }
-    } else {
-      Ok(TableProviderFilterPushDown::Unsupported)
+    match pushed_filter(filter) {
+      Some((name, op, _)) if self.capabilities().supports_filter(name, op) => match op {
+        // datasources may only approximate patterns
+        FilterOperator::Like => Ok(TableProviderFilterPushDown::Inexact),
+        _ => Ok(TableProviderFilterPushDown::Exact),
+      },
+      _ => Ok(TableProviderFilterPushDown::Unsupported),
     }
   }
 
   fn scan(
     &self,
     projection: &Option<Vec<usize>>,
-    _batch_size: usize,
+    batch_size: usize,
     filters: &[Expr],
-    _limit: Option<usize>,
+    limit: Option<usize>,
   ) -> Result<Arc<dyn ExecutionPlan>> {
+    let missing = self.missing_filters(filters);
+    if !missing.is_empty() {
+      return Err(DataFusionError::Plan(format!(
+        "missing required filters: {}",
+        missing
+          .iter()
+          .map(|name| format!("{} = <value>", name))
+          .collect::<Vec<String>>()
+          .join(" AND ")
+      )));
+    }
+
     Ok(Arc::new(JSONExec::new(
       self.datasource.clone(),
       filters.to_vec(),
       projection,
+      batch_size,
+      limit,
+      current_query_context(),
+      self.statistics.clone(),
     )))
   }
 
+  /// The statistics of the datasource, or those learned from a scan of the whole table, as
+  /// tables aren't told the filters of the scan they're asked for.
   fn statistics(&self) -> Statistics {
-    self.statistics.clone()
+    self
+      .datasource
+      .statistics()
+      .or_else(|| self.statistics.get(&StatisticsKey::default()))
+      .unwrap_or_default()
   }
 }
@@ -0,0 +1,77 @@
+use std::time::Duration;
+
+use arrow::array::{Array, BooleanArray, Int64Array};
+use arrow::compute::{cast, filter_record_batch};
+use arrow::datatypes::{DataType, TimeUnit};
+use arrow::error::{ArrowError, Result as ArrowResult};
+use arrow::record_batch::RecordBatch;
+use chrono::{DateTime, TimeZone, Utc};
+
+use crate::proto::pluginv2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TimeRange {
+  pub from: DateTime<Utc>,
+  pub to: DateTime<Utc>,
+}
+
+impl From<pluginv2::TimeRange> for TimeRange {
+  fn from(r: pluginv2::TimeRange) -> Self {
+    Self {
+      from: Utc.timestamp_millis(r.from_epoch_ms),
+      to: Utc.timestamp_millis(r.to_epoch_ms),
+    }
+  }
+}
+
+impl TimeRange {
+  /// Widens the range to the multiples of `step` around it, so that ranges relative to now are
+  /// the same for every query in a step.
+  pub(crate) fn align(&self, step: Duration) -> Self {
+    let step = step.as_millis() as i64;
+    if step == 0 {
+      return *self;
+    }
+
+    let (from, to) = (self.from.timestamp_millis(), self.to.timestamp_millis());
+    Self {
+      from: Utc.timestamp_millis(from - from.rem_euclid(step)),
+      to: Utc.timestamp_millis(to + (step - to.rem_euclid(step)) % step),
+    }
+  }
+
+  fn bounds(&self, unit: &TimeUnit) -> (i64, i64) {
+    let (from, to) = (self.from.timestamp_millis(), self.to.timestamp_millis());
+    match unit {
+      TimeUnit::Second => (from / 1000, to / 1000),
+      TimeUnit::Millisecond => (from, to),
+      TimeUnit::Microsecond => (from * 1000, to * 1000),
+      TimeUnit::Nanosecond => (from * 1000 * 1000, to * 1000 * 1000),
+    }
+  }
+
+  /// Removes the rows of `batch` whose time column falls outside of the range.
+  pub(crate) fn filter(&self, batch: &RecordBatch, column: usize) -> ArrowResult<RecordBatch> {
+    let (from, to) = match batch.schema().field(column).data_type() {
+      DataType::Timestamp(unit, _) => self.bounds(unit),
+      t => {
+        return Err(ArrowError::InvalidArgumentError(format!(
+          "time column must be a timestamp, found {:?}",
+          t
+        )))
+      }
+    };
+
+    let values = cast(batch.column(column), &DataType::Int64)?;
+    let values = values
+      .as_any()
+      .downcast_ref::<Int64Array>()
+      .ok_or_else(|| ArrowError::CastError("unable to read time column".to_owned()))?;
+
+    let mask: BooleanArray = (0..values.len())
+      .map(|i| Some(values.is_valid(i) && values.value(i) >= from && values.value(i) <= to))
+      .collect();
+
+    filter_record_batch(batch, &mask)
+  }
+}
@@ -3,5 +3,18 @@ mod plugin;
 pub mod proto;
 mod service;
 
-pub use crate::datafusion::{DataSource, JSONTableProvider};
-pub use crate::plugin::{start, DataProvider, DiagnosticsProvider, HealthStatus, Plugin, Query};
+pub use crate::datafusion::{
+  column_requested, current_plugin_context, BatchStream, CacheConfig, CacheStats, CachedDataSource,
+  ColumnStatistics, DataSource, DiskCacheConfig, FanOut, FilterCapability, FilterOperator,
+  IncrementalCapability, JSONTableProvider, Page, PageToken, PagedDataSource, Pages, Partitioner,
+  PayloadFormat, RateLimitStats, RateLimiter, RetryDataSource, RetryPolicy, RetryableError,
+  RowDataSource, Rows, Statistics, StreamingDataSource, TableCapabilities, TableSchema, TimeRange,
+  COLUMNS_OPTION, LIMIT_OPTION, SINCE_OPTION,
+};
+pub use crate::plugin::{
+  start, DataProvider, DiagnosticsProvider, HealthCheck, HealthStatus, Plugin, Query, Reporter,
+};
+pub use grafana_plugin_sdk_derive::GrafanaTable;
+
+// re-exported for the code generated by `GrafanaTable`
+pub use arrow;
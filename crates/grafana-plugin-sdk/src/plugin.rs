@@ -3,9 +3,10 @@ mod diagnostic;
 
 use std::net::SocketAddr;
 use std::net::TcpListener;
-use std::sync::Arc;
+use std::sync::{Arc, RwLock};
 
 use anyhow::{anyhow, Result};
+use arrow::datatypes::Schema;
 use arrow::record_batch::RecordBatch;
 use async_trait::async_trait;
 use datafusion::catalog::catalog::MemoryCatalogProvider;
@@ -15,13 +16,20 @@ use datafusion::prelude::*;
 use tokio::sync::Mutex;
 use tonic::transport::Server;
 
+use crate::datafusion::{QueryContext, TimeRange, QUERY_CONTEXT};
+use crate::proto::pluginv2::collect_metrics_response::Payload;
 use crate::proto::pluginv2::data_server::DataServer;
 use crate::proto::pluginv2::diagnostics_server::DiagnosticsServer;
+use crate::proto::pluginv2::PluginContext;
 use crate::service::DataService;
 use crate::service::DiagnosticsService;
 
 pub use data::{DataProvider, Query};
-pub use diagnostic::{CheckHealthResponse, DiagnosticsProvider, HealthStatus};
+use diagnostic::{health_details, prometheus_metrics};
+pub use diagnostic::{
+  CheckHealthResponse, CollectMetricsResponse, DiagnosticsProvider, HealthCheck, HealthStatus,
+  Reporter,
+};
 
 fn get_addr() -> Result<SocketAddr> {
   Ok(TcpListener::bind("127.0.0.1:0")?.local_addr()?)
@@ -31,6 +39,8 @@ fn get_addr() -> Result<SocketAddr> {
 pub struct Plugin {
   name: String,
   ctx: Arc<Mutex<ExecutionContext>>,
+  reporters: Arc<RwLock<Vec<(String, Arc<dyn Reporter>)>>>,
+  health_checks: Arc<RwLock<Vec<Arc<dyn HealthCheck>>>>,
 }
 
 #[async_trait]
@@ -39,19 +49,96 @@ impl DataProvider for Plugin {
     let ctx = Arc::clone(&self.ctx);
     let mut lock = ctx.lock().await;
     let df = lock.sql(query.sql.as_str())?;
-    let result: Vec<RecordBatch> = df.collect().await?;
-    Ok(result)
+
+    // queries without a time range are sent with a zeroed one
+    let time_range = match query.ignore_time_range || query.time_range == Default::default() {
+      true => None,
+      false => Some(TimeRange::from(query.time_range.clone())),
+    };
+
+    let context = QueryContext {
+      time_range,
+      skip_invalid_rows: query.skip_invalid_rows,
+      plugin_context: query.plugin_context.clone(),
+      ..Default::default()
+    };
+
+    let result: Vec<RecordBatch> = QUERY_CONTEXT.scope(context.clone(), df.collect()).await?;
+
+    // notices are attached to the frames as grafana frame metadata
+    let metadata = match context.diagnostics.frame_meta() {
+      Some(m) => m,
+      None => return Ok(result),
+    };
+
+    if result.is_empty() {
+      let schema = Schema::new_with_metadata(vec![], metadata);
+      return Ok(vec![RecordBatch::new_empty(Arc::new(schema))]);
+    }
+
+    result
+      .into_iter()
+      .map(|batch| {
+        let schema = Schema::new_with_metadata(batch.schema().fields().clone(), metadata.clone());
+        Ok(RecordBatch::try_new(
+          Arc::new(schema),
+          batch.columns().to_vec(),
+        )?)
+      })
+      .collect()
   }
 }
 
-// TODO: allow plugin to implement
 #[async_trait]
 impl DiagnosticsProvider for Plugin {
-  async fn check_health(&self) -> CheckHealthResponse {
+  async fn check_health(&self, plugin_context: Option<PluginContext>) -> CheckHealthResponse {
+    let details = match self.reporters.read() {
+      Ok(reporters) if !reporters.is_empty() => health_details(&reporters).to_string(),
+      _ => "".to_string(),
+    };
+
+    // the lock isn't held while the checks run
+    let checks = match self.health_checks.read() {
+      Ok(checks) => checks.clone(),
+      Err(_) => vec![],
+    };
+
+    let mut messages = vec![];
+    for check in checks {
+      match check.check(plugin_context.as_ref()).await {
+        Ok(message) => messages.push(message),
+        Err(e) => {
+          return CheckHealthResponse {
+            status: HealthStatus::Error.into(),
+            message: e.to_string(),
+            json_details: details.into_bytes(),
+          }
+        }
+      }
+    }
+
+    let message = match messages.is_empty() {
+      true => "Ok".to_string(),
+      false => messages.join("\n"),
+    };
+
     CheckHealthResponse {
       status: HealthStatus::Ok.into(),
-      message: "Ok".to_string(),
-      json_details: vec![],
+      message,
+      json_details: details.into_bytes(),
+    }
+  }
+
+  async fn collect_metrics(&self) -> CollectMetricsResponse {
+    let metrics = match self.reporters.read() {
+      Ok(reporters) => prometheus_metrics(&reporters),
+      Err(_) => "".to_string(),
+    };
+
+    CollectMetricsResponse {
+      metrics: Some(Payload {
+        prometheus: metrics.into_bytes(),
+      }),
     }
   }
 }
@@ -69,9 +156,31 @@ impl Plugin {
     Self {
       name: name.to_owned(),
       ctx: Arc::new(Mutex::new(ctx)),
+      reporters: Arc::new(RwLock::new(vec![])),
+      health_checks: Arc::new(RwLock::new(vec![])),
     }
   }
 
+  /// Includes the state of a component, e.g. a cache, in health checks and metrics.
+  pub fn register_reporter(&self, name: &str, reporter: Arc<dyn Reporter>) -> Result<()> {
+    self
+      .reporters
+      .write()
+      .map_err(|_| anyhow!("reporters lock poisoned"))?
+      .push((name.to_owned(), reporter));
+    Ok(())
+  }
+
+  /// Runs `check` in health checks, which fail if it returns an error.
+  pub fn register_health_check(&self, check: Arc<dyn HealthCheck>) -> Result<()> {
+    self
+      .health_checks
+      .write()
+      .map_err(|_| anyhow!("health checks lock poisoned"))?
+      .push(check);
+    Ok(())
+  }
+
   pub async fn register_table(
     &self,
     table_name: String,
@@ -5,7 +5,7 @@ use arrow::record_batch::RecordBatch;
 use async_trait::async_trait;
 use serde::Deserialize;
 
-use crate::proto::pluginv2::{DataQuery, TimeRange};
+use crate::proto::pluginv2::{DataQuery, PluginContext, TimeRange};
 
 #[derive(PartialEq, Clone)]
 pub struct Query {
@@ -15,11 +15,23 @@ pub struct Query {
   pub time_range: TimeRange,
   pub max_data_points: i64,
   pub sql: String,
+  pub ignore_time_range: bool,
+  /// Drops rows that don't match the table schema, with a notice, instead of failing the query.
+  ///
+  /// Only rows that are checked one at a time can be dropped: those of row datasources and JSON
+  /// payloads. CSV, Arrow IPC and Parquet payloads are decoded whole, and fail on any invalid row.
+  pub skip_invalid_rows: bool,
+  pub plugin_context: Option<PluginContext>,
 }
 
 #[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
 pub struct DataQueryJSON {
   sql: String,
+  #[serde(default)]
+  ignore_time_range: bool,
+  #[serde(default)]
+  skip_invalid_rows: bool,
 }
 
 impl TryFrom<DataQuery> for Query {
@@ -34,6 +46,9 @@ impl TryFrom<DataQuery> for Query {
       time_range: q.time_range.unwrap_or(TimeRange::default()).clone(),
       max_data_points: q.max_data_points.clone(),
       sql: json.sql.clone(),
+      ignore_time_range: json.ignore_time_range,
+      skip_invalid_rows: json.skip_invalid_rows,
+      plugin_context: None,
     });
   }
 }
@@ -1,9 +1,93 @@
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use anyhow::Result;
 use async_trait::async_trait;
+use serde_json::{Map, Value};
 
 pub use crate::proto::pluginv2::check_health_response::HealthStatus;
-pub use crate::proto::pluginv2::CheckHealthResponse;
+use crate::proto::pluginv2::PluginContext;
+pub use crate::proto::pluginv2::{CheckHealthResponse, CollectMetricsResponse};
 
 #[async_trait]
 pub trait DiagnosticsProvider {
-  async fn check_health(&self) -> CheckHealthResponse;
+  /// Checks the health of the datasource instance in `plugin_context`, e.g. when its settings
+  /// are saved.
+  async fn check_health(&self, plugin_context: Option<PluginContext>) -> CheckHealthResponse;
+
+  async fn collect_metrics(&self) -> CollectMetricsResponse {
+    CollectMetricsResponse { metrics: None }
+  }
+}
+
+/// A component whose state is included in health checks and metrics, e.g. a cache.
+pub trait Reporter: Send + Sync {
+  /// The kind of component, which prefixes its metric names, e.g. `cache`.
+  fn kind(&self) -> &'static str;
+
+  /// The current state of the component. Its numeric fields are also exported as metrics.
+  fn report(&self) -> Value;
+}
+
+/// Checks the settings of a datasource instance, e.g. that its credentials are valid.
+#[async_trait]
+pub trait HealthCheck: Send + Sync {
+  /// A message describing the healthy instance, or the error that makes it unhealthy.
+  async fn check(&self, plugin_context: Option<&PluginContext>) -> Result<String>;
+}
+
+/// The reports of every component, keyed by their name and kind.
+pub(crate) fn health_details(reporters: &[(String, Arc<dyn Reporter>)]) -> Value {
+  let mut details = Map::new();
+  for (name, reporter) in reporters {
+    let entry = details
+      .entry(name.clone())
+      .or_insert_with(|| Value::Object(Map::new()));
+    if let Value::Object(kinds) = entry {
+      kinds.insert(reporter.kind().to_owned(), reporter.report());
+    }
+  }
+  Value::Object(details)
+}
+
+/// The numeric fields of every report in the prometheus text format.
+pub(crate) fn prometheus_metrics(reporters: &[(String, Arc<dyn Reporter>)]) -> String {
+  // samples are grouped by metric, as prometheus expects
+  let mut metrics: BTreeMap<String, Vec<(String, f64)>> = BTreeMap::new();
+
+  for (name, reporter) in reporters {
+    let fields = match reporter.report() {
+      Value::Object(fields) => fields,
+      _ => continue,
+    };
+
+    for (field, value) in fields {
+      if let Some(value) = value.as_f64() {
+        let metric = format!("grafana_plugin_{}_{}", reporter.kind(), snake_case(&field));
+        metrics
+          .entry(metric)
+          .or_default()
+          .push((name.clone(), value));
+      }
+    }
+  }
+
+  let mut text = String::new();
+  for (metric, samples) in metrics {
+    for (name, value) in samples {
+      text.push_str(&format!("{}{{name=\"{}\"}} {}\n", metric, name, value));
+    }
+  }
+  text
+}
+
+fn snake_case(name: &str) -> String {
+  let mut snake = String::new();
+  for c in name.chars() {
+    if c.is_uppercase() {
+      snake.push('_');
+    }
+    snake.extend(c.to_lowercase());
+  }
+  snake
 }
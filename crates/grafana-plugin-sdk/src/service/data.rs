@@ -11,7 +11,9 @@ use tokio::sync::RwLock;
 
 use crate::plugin::{DataProvider, Query};
 use crate::proto::pluginv2::data_server::Data;
-use crate::proto::pluginv2::{DataQuery, DataResponse, QueryDataRequest, QueryDataResponse};
+use crate::proto::pluginv2::{
+  DataQuery, DataResponse, PluginContext, QueryDataRequest, QueryDataResponse,
+};
 
 pub struct DataService<P> {
   provider: P,
@@ -40,11 +42,15 @@ impl<P: DataProvider> DataService<P> {
     Ok(v)
   }
 
-  async fn handle_query(&self, q: &DataQuery) -> Result<Arc<Vec<Vec<u8>>>> {
-    let batches = self
-      .provider
-      .handle_query(Query::try_from(q.clone())?)
-      .await?;
+  async fn handle_query(
+    &self,
+    q: &DataQuery,
+    plugin_context: &Option<PluginContext>,
+  ) -> Result<Arc<Vec<Vec<u8>>>> {
+    let mut query = Query::try_from(q.clone())?;
+    query.plugin_context = plugin_context.clone();
+
+    let batches = self.provider.handle_query(query).await?;
 
     let mut frames = vec![];
 
@@ -63,10 +69,11 @@ impl<P: DataProvider + Sync + Send + 'static> Data for DataService<P> {
     request: tonic::Request<QueryDataRequest>,
   ) -> Result<tonic::Response<QueryDataResponse>, tonic::Status> {
     let mut responses: HashMap<String, DataResponse> = HashMap::new();
-    for query in request.into_inner().queries.iter() {
+    let request = request.into_inner();
+    for query in request.queries.iter() {
       let mut frames = Arc::new(vec![]);
       let mut error = "".to_string();
-      let results = self.handle_query(query).await;
+      let results = self.handle_query(query, &request.plugin_context).await;
 
       match results {
         Ok(f) => frames = Arc::clone(&f),
@@ -19,21 +19,20 @@ impl<P: DiagnosticsProvider> DiagnosticsService<P> {
 
 #[async_trait]
 impl<P: DiagnosticsProvider + Sync + Send + 'static> Diagnostics for DiagnosticsService<P> {
-  // TODO: pass request to provider
   async fn check_health(
     &self,
-    _request: tonic::Request<CheckHealthRequest>,
+    request: tonic::Request<CheckHealthRequest>,
   ) -> Result<tonic::Response<CheckHealthResponse>, tonic::Status> {
-    Ok(tonic::Response::new(self.provider.check_health().await))
+    let plugin_context = request.into_inner().plugin_context;
+    Ok(tonic::Response::new(
+      self.provider.check_health(plugin_context).await,
+    ))
   }
 
-  // TODO: implement
   async fn collect_metrics(
     &self,
     _request: tonic::Request<CollectMetricsRequest>,
   ) -> Result<tonic::Response<CollectMetricsResponse>, tonic::Status> {
-    Ok(tonic::Response::new(CollectMetricsResponse {
-      metrics: None,
-    }))
+    Ok(tonic::Response::new(self.provider.collect_metrics().await))
   }
 }
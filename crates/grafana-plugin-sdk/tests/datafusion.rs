@@ -0,0 +1,837 @@
+use std::collections::HashMap;
+use std::io::Cursor;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anyhow::Result;
+use arrow::array::{
+  Array, Int64Array, ListArray, StringArray, TimestampNanosecondArray, TimestampSecondArray,
+};
+use arrow::datatypes::{DataType, Field, Schema, SchemaRef};
+use arrow::record_batch::RecordBatch;
+use async_trait::async_trait;
+use chrono::{DateTime, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use datafusion::datasource::TableProvider;
+use datafusion::logical_plan::{col, lit};
+use datafusion::physical_plan::ExecutionPlan;
+use futures::stream::{self, StreamExt};
+use grafana_plugin_sdk::proto::pluginv2;
+use grafana_plugin_sdk::{
+  BatchStream, CacheConfig, CachedDataSource, DataProvider, DataSource, DiskCacheConfig, FanOut,
+  GrafanaTable, JSONTableProvider, Page, PageToken, PagedDataSource, Pages, Partitioner, Plugin,
+  Query, StreamingDataSource, TableCapabilities, TableSchema, TimeRange, SINCE_OPTION,
+};
+use serde::Serialize;
+
+fn schema() -> SchemaRef {
+  Arc::new(Schema::new(vec![
+    Field::new("id", DataType::Int64, false),
+    Field::new("name", DataType::Utf8, true),
+    Field::new("active", DataType::Boolean, true),
+  ]))
+}
+
+#[derive(Debug, Clone)]
+struct TestSource(&'static str);
+
+#[async_trait]
+impl DataSource for TestSource {
+  type Data = Cursor<Vec<u8>>;
+
+  async fn fetch_results(
+    &self,
+    _options: HashMap<String, String>,
+    _time_range: Option<TimeRange>,
+  ) -> Result<Self::Data> {
+    Ok(Cursor::new(self.0.as_bytes().to_vec()))
+  }
+
+  fn schema(&self) -> SchemaRef {
+    schema()
+  }
+}
+
+// counts its fetches
+#[derive(Debug, Clone, Default)]
+struct CountingSource(Arc<AtomicUsize>);
+
+#[async_trait]
+impl DataSource for CountingSource {
+  type Data = Cursor<Vec<u8>>;
+
+  async fn fetch_results(
+    &self,
+    _options: HashMap<String, String>,
+    _time_range: Option<TimeRange>,
+  ) -> Result<Self::Data> {
+    self.0.fetch_add(1, Ordering::SeqCst);
+    Ok(Cursor::new(ROWS.as_bytes().to_vec()))
+  }
+
+  fn schema(&self) -> SchemaRef {
+    schema()
+  }
+}
+
+// produces batches that don't match the schema it reports
+#[derive(Debug, Clone)]
+struct MismatchedSource;
+
+#[async_trait]
+impl StreamingDataSource for MismatchedSource {
+  async fn fetch_stream(
+    &self,
+    _options: HashMap<String, String>,
+    _time_range: Option<TimeRange>,
+  ) -> Result<BatchStream> {
+    let schema = Arc::new(Schema::new(vec![Field::new("id", DataType::Utf8, false)]));
+    let batch = RecordBatch::try_new(
+      schema,
+      vec![Arc::new(arrow::array::StringArray::from(vec!["1"]))],
+    )?;
+
+    Ok(stream::iter(vec![Ok(batch)]).boxed())
+  }
+
+  fn schema(&self) -> SchemaRef {
+    schema()
+  }
+}
+
+#[derive(Serialize, GrafanaTable)]
+struct LabeledRow {
+  id: i64,
+  labels: Vec<String>,
+  reviewers: Option<Vec<String>>,
+}
+
+#[derive(Debug, Clone)]
+struct LabeledSource;
+
+#[async_trait]
+impl PagedDataSource for LabeledSource {
+  type Row = LabeledRow;
+
+  async fn fetch_page(
+    &self,
+    _options: &HashMap<String, String>,
+    _time_range: Option<TimeRange>,
+    _page: PageToken,
+  ) -> Result<Page<Self::Row>> {
+    let rows = vec![
+      LabeledRow {
+        id: 1,
+        labels: vec!["bug".to_owned(), "help wanted".to_owned()],
+        reviewers: None,
+      },
+      LabeledRow {
+        id: 2,
+        labels: vec![],
+        reviewers: Some(vec!["a".to_owned()]),
+      },
+    ];
+
+    Ok(Page {
+      rows,
+      next: None,
+      total_pages: None,
+    })
+  }
+
+  fn schema(&self) -> SchemaRef {
+    LabeledRow::table_schema()
+  }
+}
+
+#[derive(Serialize, GrafanaTable)]
+struct DatedRow {
+  id: i64,
+  #[table(time)]
+  created_at: DateTime<Utc>,
+}
+
+// a row created at each of its times
+#[derive(Debug, Clone)]
+struct DatedSource(&'static [&'static str]);
+
+#[async_trait]
+impl PagedDataSource for DatedSource {
+  type Row = DatedRow;
+
+  async fn fetch_page(
+    &self,
+    _options: &HashMap<String, String>,
+    _time_range: Option<TimeRange>,
+    _page: PageToken,
+  ) -> Result<Page<Self::Row>> {
+    let rows = self
+      .0
+      .iter()
+      .zip(1..)
+      .map(|(t, id)| {
+        Ok(DatedRow {
+          id,
+          created_at: DateTime::parse_from_rfc3339(t)?.with_timezone(&Utc),
+        })
+      })
+      .collect::<Result<Vec<DatedRow>>>()?;
+
+    Ok(Page {
+      rows,
+      next: None,
+      total_pages: None,
+    })
+  }
+
+  fn schema(&self) -> SchemaRef {
+    DatedRow::table_schema()
+  }
+}
+
+#[derive(Serialize, GrafanaTable)]
+struct NaiveRow {
+  started_at: NaiveDateTime,
+  day: NaiveDate,
+}
+
+#[derive(Debug, Clone)]
+struct NaiveSource;
+
+#[async_trait]
+impl PagedDataSource for NaiveSource {
+  type Row = NaiveRow;
+
+  async fn fetch_page(
+    &self,
+    _options: &HashMap<String, String>,
+    _time_range: Option<TimeRange>,
+    _page: PageToken,
+  ) -> Result<Page<Self::Row>> {
+    let day = NaiveDate::from_ymd(2021, 6, 14);
+    Ok(Page {
+      rows: vec![NaiveRow {
+        started_at: day.and_hms_milli(15, 32, 8, 500),
+        day,
+      }],
+      next: None,
+      total_pages: None,
+    })
+  }
+
+  fn schema(&self) -> SchemaRef {
+    NaiveRow::table_schema()
+  }
+}
+
+#[derive(Serialize, GrafanaTable, Debug, Clone)]
+struct TrackedRow {
+  #[table(key)]
+  id: i64,
+  #[table(filter)]
+  state: String,
+  #[table(watermark)]
+  updated_at: DateTime<Utc>,
+}
+
+// rows that can change between fetches, filtered on their state and update time
+#[derive(Debug, Clone, Default)]
+struct TrackedSource(Arc<Mutex<Vec<TrackedRow>>>);
+
+#[async_trait]
+impl PagedDataSource for TrackedSource {
+  type Row = TrackedRow;
+
+  async fn fetch_page(
+    &self,
+    options: &HashMap<String, String>,
+    _time_range: Option<TimeRange>,
+    _page: PageToken,
+  ) -> Result<Page<Self::Row>> {
+    let since = match options.get(SINCE_OPTION) {
+      Some(since) => Some(DateTime::parse_from_rfc3339(since)?.with_timezone(&Utc)),
+      None => None,
+    };
+    let rows = self
+      .0
+      .lock()
+      .unwrap()
+      .iter()
+      .filter(|r| options.get("state").map_or(true, |s| *s == r.state))
+      .filter(|r| since.map_or(true, |since| r.updated_at >= since))
+      .cloned()
+      .collect();
+
+    Ok(Page {
+      rows,
+      next: None,
+      total_pages: None,
+    })
+  }
+
+  fn schema(&self) -> SchemaRef {
+    TrackedRow::table_schema()
+  }
+
+  fn capabilities(&self) -> TableCapabilities {
+    TrackedRow::capabilities()
+  }
+}
+
+#[derive(Serialize)]
+struct Author {
+  login: String,
+}
+
+#[derive(Serialize, GrafanaTable)]
+struct NestedRow {
+  id: i64,
+  #[table(skip)]
+  author: Author,
+  #[table(skip)]
+  reactions: HashMap<String, i64>,
+  #[table(skip)]
+  span: (i64, i64),
+}
+
+// rows with fields no column can hold, which aren't in the schema
+#[derive(Debug, Clone)]
+struct NestedSource;
+
+#[async_trait]
+impl PagedDataSource for NestedSource {
+  type Row = NestedRow;
+
+  async fn fetch_page(
+    &self,
+    _options: &HashMap<String, String>,
+    _time_range: Option<TimeRange>,
+    _page: PageToken,
+  ) -> Result<Page<Self::Row>> {
+    let mut reactions = HashMap::new();
+    reactions.insert("+1".to_owned(), 3);
+    let rows = vec![NestedRow {
+      id: 1,
+      author: Author {
+        login: "a".to_owned(),
+      },
+      reactions,
+      span: (10, 12),
+    }];
+
+    Ok(Page {
+      rows,
+      next: None,
+      total_pages: None,
+    })
+  }
+
+  fn schema(&self) -> SchemaRef {
+    NestedRow::table_schema()
+  }
+}
+
+#[derive(Serialize, GrafanaTable)]
+struct OwnedRow {
+  #[table(required)]
+  owner: String,
+  name: String,
+}
+
+// a row for each name, of the owner in its options
+#[derive(Debug, Clone)]
+struct OwnedSource;
+
+#[async_trait]
+impl PagedDataSource for OwnedSource {
+  type Row = OwnedRow;
+
+  async fn fetch_page(
+    &self,
+    options: &HashMap<String, String>,
+    _time_range: Option<TimeRange>,
+    _page: PageToken,
+  ) -> Result<Page<Self::Row>> {
+    let names: &[&str] = match options["owner"].as_str() {
+      "grafana" => &["loki", "tempo", "mimir"],
+      _ => &["datafusion"],
+    };
+    let rows = names
+      .iter()
+      .map(|name| OwnedRow {
+        owner: options["owner"].clone(),
+        name: name.to_string(),
+      })
+      .collect();
+
+    Ok(Page {
+      rows,
+      next: None,
+      total_pages: None,
+    })
+  }
+
+  fn schema(&self) -> SchemaRef {
+    OwnedRow::table_schema()
+  }
+
+  fn capabilities(&self) -> TableCapabilities {
+    TableCapabilities {
+      projection: true,
+      ..OwnedRow::capabilities()
+    }
+  }
+}
+
+#[derive(Serialize, GrafanaTable)]
+struct PartRow {
+  part: String,
+}
+
+// a row for the part in its options
+#[derive(Debug, Clone)]
+struct PartSource;
+
+#[async_trait]
+impl PagedDataSource for PartSource {
+  type Row = PartRow;
+
+  async fn fetch_page(
+    &self,
+    options: &HashMap<String, String>,
+    _time_range: Option<TimeRange>,
+    _page: PageToken,
+  ) -> Result<Page<Self::Row>> {
+    Ok(Page {
+      rows: vec![PartRow {
+        part: options["part"].clone(),
+      }],
+      next: None,
+      total_pages: None,
+    })
+  }
+
+  fn schema(&self) -> SchemaRef {
+    PartRow::table_schema()
+  }
+}
+
+#[derive(Debug, Clone)]
+struct ThreeParts;
+
+#[async_trait]
+impl Partitioner for ThreeParts {
+  async fn partitions(
+    &self,
+    options: &HashMap<String, String>,
+  ) -> Result<Vec<HashMap<String, String>>> {
+    Ok(
+      ["a", "b", "c"]
+        .iter()
+        .map(|part| {
+          let mut options = options.clone();
+          options.insert("part".to_owned(), part.to_string());
+          options
+        })
+        .collect(),
+    )
+  }
+}
+
+const ROWS: &str = r#"{"id": 1, "name": "a", "active": true}
+{"id": 2, "name": "b", "active": false}
+{"id": 3, "name": null, "active": true}
+{"id": 4, "name": "d"}
+{"id": 5, "name": "e", "active": true}
+"#;
+
+async fn execute(plan: Arc<dyn ExecutionPlan>) -> datafusion::error::Result<Vec<RecordBatch>> {
+  let mut stream = plan.execute(0).await?;
+  let mut batches = Vec::new();
+  while let Some(batch) = stream.next().await {
+    batches.push(batch?);
+  }
+  Ok(batches)
+}
+
+#[tokio::test]
+async fn projection_schema_matches_batches() {
+  let provider = JSONTableProvider::new(TestSource(ROWS));
+  let plan = provider.scan(&Some(vec![2, 0]), 1024, &[], None).unwrap();
+
+  let expected = Schema::new(vec![
+    Field::new("active", DataType::Boolean, true),
+    Field::new("id", DataType::Int64, false),
+  ]);
+  assert_eq!(plan.schema().as_ref(), &expected);
+
+  let batches = execute(plan.clone()).await.unwrap();
+  assert_eq!(batches.len(), 1);
+  assert_eq!(batches[0].schema(), plan.schema());
+  assert_eq!(batches[0].num_rows(), 5);
+}
+
+#[tokio::test]
+async fn unprojected_schema_is_the_datasource_schema() {
+  let provider = JSONTableProvider::new(TestSource(ROWS));
+  let plan = provider.scan(&None, 1024, &[], None).unwrap();
+
+  assert_eq!(plan.schema(), schema());
+
+  let batches = execute(plan).await.unwrap();
+  assert_eq!(batches[0].schema(), schema());
+}
+
+#[tokio::test]
+async fn batches_honor_batch_size() {
+  let provider = JSONTableProvider::new(TestSource(ROWS));
+  let plan = provider.scan(&Some(vec![0]), 2, &[], None).unwrap();
+
+  let batches = execute(plan).await.unwrap();
+  let sizes: Vec<usize> = batches.iter().map(|b| b.num_rows()).collect();
+  assert_eq!(sizes, vec![2, 2, 1]);
+}
+
+#[tokio::test]
+async fn incremental_results_are_fetched_in_full_again() {
+  let source = TrackedSource::default();
+  let created = Utc.ymd(2021, 6, 1).and_hms(0, 0, 0);
+  for id in 1..=2 {
+    source.0.lock().unwrap().push(TrackedRow {
+      id,
+      state: "open".to_owned(),
+      updated_at: created,
+    });
+  }
+
+  let config = CacheConfig {
+    ttl: Duration::from_secs(0),
+    max_incremental_age: Duration::from_millis(200),
+    ..Default::default()
+  };
+  let cached = CachedDataSource::new(Pages::new(source.clone()), config);
+  let provider = JSONTableProvider::new(cached.clone());
+  let open_ids = || async {
+    let filters = vec![col("state").eq(lit("open"))];
+    let plan = provider.scan(&Some(vec![0]), 1024, &filters, None).unwrap();
+    let batches = execute(plan).await.unwrap();
+    batches
+      .iter()
+      .flat_map(|b| {
+        let ids = b.column(0).as_any().downcast_ref::<Int64Array>().unwrap();
+        (0..ids.len()).map(|i| ids.value(i)).collect::<Vec<i64>>()
+      })
+      .collect::<Vec<i64>>()
+  };
+
+  assert_eq!(open_ids().await, vec![1, 2]);
+
+  // the closed row no longer matches, so refreshes with the changed rows don't replace it,
+  // while the rows that still match are fetched again and moved to the end
+  {
+    let mut rows = source.0.lock().unwrap();
+    rows[1].state = "closed".to_owned();
+    rows[1].updated_at = created + chrono::Duration::hours(1);
+  }
+  assert_eq!(open_ids().await, vec![2, 1]);
+  assert_eq!(cached.stats().incremental_refreshes, 1);
+
+  tokio::time::sleep(Duration::from_millis(300)).await;
+  assert_eq!(open_ids().await, vec![1]);
+  assert_eq!(cached.stats().incremental_refreshes, 1);
+}
+
+#[tokio::test]
+async fn invalidated_results_are_not_read_from_disk() {
+  let directory = std::env::temp_dir().join(format!("sdk-invalidate-{}", std::process::id()));
+  let source = CountingSource::default();
+  let config = CacheConfig {
+    disk: Some(DiskCacheConfig::new(&directory)),
+    ..Default::default()
+  };
+  let cached = CachedDataSource::new(source.clone(), config);
+  let provider = JSONTableProvider::new(cached.clone());
+
+  let plan = provider.scan(&None, 1024, &[], None).unwrap();
+  execute(plan).await.unwrap();
+  cached.invalidate();
+  let plan = provider.scan(&None, 1024, &[], None).unwrap();
+  execute(plan).await.unwrap();
+
+  let stats = cached.stats();
+  std::fs::remove_dir_all(&directory).ok();
+  assert_eq!(source.0.load(Ordering::SeqCst), 2);
+  assert_eq!(
+    (stats.misses, stats.disk_hits, stats.disk_errors),
+    (2, 0, 0)
+  );
+}
+
+#[tokio::test]
+async fn statistics_are_only_learned_from_the_whole_table() {
+  let provider = JSONTableProvider::new(TestSource(ROWS));
+  let filters = vec![col("name").eq(lit("a"))];
+  let plan = provider.scan(&None, 1024, &filters, None).unwrap();
+  execute(plan).await.unwrap();
+  assert_eq!(provider.statistics().num_rows, None);
+
+  let plan = provider.scan(&None, 1024, &[], None).unwrap();
+  execute(plan).await.unwrap();
+  assert_eq!(provider.statistics().num_rows, Some(5));
+}
+
+#[tokio::test]
+async fn statistics_are_learned_for_each_set_of_filters() {
+  let provider = JSONTableProvider::new(Pages::new(OwnedSource));
+  let grafana = vec![col("owner").eq(lit("grafana"))];
+  let apache = vec![col("owner").eq(lit("apache"))];
+
+  // the columns a scan reads don't change its rows
+  let plan = provider.scan(&Some(vec![1]), 1024, &grafana, None).unwrap();
+  execute(plan).await.unwrap();
+  assert_eq!(provider.scan_statistics(&grafana).num_rows, Some(3));
+  assert_eq!(provider.scan_statistics(&apache).num_rows, None);
+  // the table can't be scanned without an owner, so it has no statistics of its own
+  assert_eq!(provider.statistics().num_rows, None);
+
+  let plan = provider.scan(&None, 1024, &apache, None).unwrap();
+  execute(plan).await.unwrap();
+  assert_eq!(provider.scan_statistics(&apache).num_rows, Some(1));
+  assert_eq!(provider.scan_statistics(&grafana).num_rows, Some(3));
+}
+
+#[tokio::test]
+async fn empty_results() {
+  let provider = JSONTableProvider::new(TestSource(""));
+  let plan = provider.scan(&Some(vec![1]), 1024, &[], None).unwrap();
+
+  let batches = execute(plan).await.unwrap();
+  let rows: usize = batches.iter().map(|b| b.num_rows()).sum();
+  assert_eq!(rows, 0);
+}
+
+#[tokio::test]
+async fn invalid_rows_fail_the_query() {
+  let provider = JSONTableProvider::new(TestSource(
+    r#"{"id": 1, "name": "a"}
+{"id": "two", "name": "b"}
+"#,
+  ));
+  let plan = provider.scan(&None, 1024, &[], None).unwrap();
+
+  let err = execute(plan).await.unwrap_err();
+  assert!(err.to_string().contains("invalid row 2"), "{}", err);
+}
+
+#[tokio::test]
+async fn mismatched_batch_schema_fails_the_query() {
+  let provider = JSONTableProvider::new(MismatchedSource);
+  let plan = provider.scan(&None, 1024, &[], None).unwrap();
+
+  assert!(execute(plan).await.is_err());
+}
+
+#[tokio::test]
+async fn sequences_are_encoded_as_lists() {
+  let provider = JSONTableProvider::new(Pages::new(LabeledSource));
+  let plan = provider.scan(&None, 1024, &[], None).unwrap();
+
+  let batches = execute(plan).await.unwrap();
+  assert_eq!(batches.len(), 1);
+
+  let labels = batches[0]
+    .column(1)
+    .as_any()
+    .downcast_ref::<ListArray>()
+    .unwrap();
+  let first = labels.value(0);
+  let first = first.as_any().downcast_ref::<StringArray>().unwrap();
+  assert_eq!(first.value(0), "bug");
+  assert_eq!(first.value(1), "help wanted");
+  assert_eq!(labels.value_length(1), 0);
+
+  let reviewers = batches[0].column(2);
+  assert!(reviewers.is_null(0));
+  assert!(reviewers.is_valid(1));
+}
+
+#[tokio::test]
+async fn fields_that_are_not_columns_are_skipped() {
+  let provider = JSONTableProvider::new(Pages::new(NestedSource));
+  let plan = provider.scan(&None, 1024, &[], None).unwrap();
+
+  let batches = execute(plan).await.unwrap();
+  assert_eq!(batches[0].num_columns(), 1);
+  let ids = batches[0]
+    .column(0)
+    .as_any()
+    .downcast_ref::<Int64Array>()
+    .unwrap();
+  assert_eq!(ids.value(0), 1);
+}
+
+#[tokio::test]
+async fn times_are_encoded_as_nanoseconds() {
+  let source = DatedSource(&["2021-06-14T15:32:08.5Z", "1969-12-31T23:59:59.5Z"]);
+  let provider = JSONTableProvider::new(Pages::new(source));
+  let plan = provider.scan(&None, 1024, &[], None).unwrap();
+
+  let batches = execute(plan).await.unwrap();
+  let times = batches[0]
+    .column(1)
+    .as_any()
+    .downcast_ref::<TimestampNanosecondArray>()
+    .unwrap();
+  assert_eq!(times.value(0), 1_623_684_728_500_000_000);
+  assert_eq!(times.value(1), -500_000_000);
+}
+
+#[tokio::test]
+async fn times_out_of_range_fail_the_row() {
+  let source = DatedSource(&["2021-06-14T15:32:08Z", "0001-01-01T00:00:00Z"]);
+  let provider = JSONTableProvider::new(Pages::new(source));
+  let plan = provider.scan(&None, 1024, &[], None).unwrap();
+
+  let err = execute(plan).await.unwrap_err();
+  assert!(err.to_string().contains("invalid row 2"), "{}", err);
+}
+
+#[tokio::test]
+async fn naive_times_are_encoded_as_utc() {
+  let provider = JSONTableProvider::new(Pages::new(NaiveSource));
+  let plan = provider.scan(&None, 1024, &[], None).unwrap();
+
+  let batches = execute(plan).await.unwrap();
+  let started_at = batches[0]
+    .column(0)
+    .as_any()
+    .downcast_ref::<TimestampNanosecondArray>()
+    .unwrap();
+  let day = batches[0]
+    .column(1)
+    .as_any()
+    .downcast_ref::<TimestampSecondArray>()
+    .unwrap();
+  assert_eq!(started_at.value(0), 1_623_684_728_500_000_000);
+  assert_eq!(day.value(0), 1_623_628_800);
+}
+
+#[tokio::test]
+async fn limits_count_the_rows_in_the_time_range() {
+  let plugin = Plugin::new("test");
+  let source = DatedSource(&[
+    "2021-06-14T00:00:00Z",
+    "2021-06-13T00:00:00Z",
+    "2021-06-02T00:00:00Z",
+    "2021-06-01T00:00:00Z",
+  ]);
+  plugin
+    .register_table(
+      "dated".to_owned(),
+      Arc::new(JSONTableProvider::new(Pages::new(source))),
+    )
+    .await
+    .unwrap();
+
+  let from = Utc.ymd(2021, 6, 1).and_hms(12, 0, 0);
+  let to = Utc.ymd(2021, 6, 3).and_hms(0, 0, 0);
+  let batches = plugin
+    .handle_query(Query {
+      ref_id: "A".to_owned(),
+      query_type: "".to_owned(),
+      interval_ms: 0,
+      time_range: pluginv2::TimeRange {
+        from_epoch_ms: from.timestamp_millis(),
+        to_epoch_ms: to.timestamp_millis(),
+      },
+      max_data_points: 0,
+      sql: "SELECT id FROM datasource.test.dated LIMIT 1".to_owned(),
+      ignore_time_range: false,
+      skip_invalid_rows: false,
+      plugin_context: None,
+    })
+    .await
+    .unwrap();
+
+  let ids: Vec<i64> = batches
+    .iter()
+    .flat_map(|b| {
+      let ids = b.column(0).as_any().downcast_ref::<Int64Array>().unwrap();
+      (0..ids.len()).map(|i| ids.value(i)).collect::<Vec<i64>>()
+    })
+    .collect();
+  assert_eq!(ids, vec![3]);
+}
+
+#[tokio::test]
+async fn relative_time_ranges_share_cached_results() {
+  let plugin = Plugin::new("test");
+  let source = DatedSource(&[
+    "2021-06-14T00:00:00Z",
+    "2021-06-02T00:00:00Z",
+    "2021-06-01T12:00:30Z",
+  ]);
+  let cached = CachedDataSource::new(Pages::new(source), CacheConfig::default());
+  plugin
+    .register_table(
+      "dated".to_owned(),
+      Arc::new(JSONTableProvider::new(cached.clone())),
+    )
+    .await
+    .unwrap();
+
+  // the same dashboard range, refreshed 30 seconds later
+  for offset in &[0, 30] {
+    let from = Utc.ymd(2021, 6, 1).and_hms(12, 1, *offset);
+    let to = Utc.ymd(2021, 6, 3).and_hms(0, 1, *offset);
+    let batches = plugin
+      .handle_query(Query {
+        ref_id: "A".to_owned(),
+        query_type: "".to_owned(),
+        interval_ms: 0,
+        time_range: pluginv2::TimeRange {
+          from_epoch_ms: from.timestamp_millis(),
+          to_epoch_ms: to.timestamp_millis(),
+        },
+        max_data_points: 0,
+        sql: "SELECT id FROM datasource.test.dated".to_owned(),
+        ignore_time_range: false,
+        skip_invalid_rows: false,
+        plugin_context: None,
+      })
+      .await
+      .unwrap();
+
+    let ids: Vec<i64> = batches
+      .iter()
+      .flat_map(|b| {
+        let ids = b.column(0).as_any().downcast_ref::<Int64Array>().unwrap();
+        (0..ids.len()).map(|i| ids.value(i)).collect::<Vec<i64>>()
+      })
+      .collect();
+    assert_eq!(ids, vec![2]);
+  }
+
+  let stats = cached.stats();
+  assert_eq!((stats.misses, stats.hits), (1, 1));
+}
+
+#[tokio::test]
+async fn partitions_are_fetched_in_order() {
+  let provider = JSONTableProvider::new(FanOut::with_concurrency(
+    Pages::new(PartSource),
+    ThreeParts,
+    2,
+  ));
+  let plan = provider.scan(&None, 1024, &[], None).unwrap();
+
+  let batches = execute(plan).await.unwrap();
+  let parts: Vec<String> = batches
+    .iter()
+    .map(|b| {
+      let parts = b.column(0).as_any().downcast_ref::<StringArray>().unwrap();
+      parts.value(0).to_owned()
+    })
+    .collect();
+  assert_eq!(parts, vec!["a", "b", "c"]);
+}
@@ -0,0 +1,207 @@
+use arrow::datatypes::{DataType, Field, TimeUnit};
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+use grafana_plugin_sdk::{
+  FilterCapability, FilterOperator, GrafanaTable, IncrementalCapability, TableCapabilities,
+  TableSchema,
+};
+use serde::Serialize;
+
+fn fields<T: TableSchema>() -> Vec<Field> {
+  T::table_schema().fields().clone()
+}
+
+fn nanoseconds() -> DataType {
+  DataType::Timestamp(TimeUnit::Nanosecond, None)
+}
+
+#[derive(Serialize, GrafanaTable)]
+#[allow(dead_code)]
+struct TypedRow {
+  flag: bool,
+  small: i8,
+  medium: i16,
+  int: i32,
+  big: i64,
+  unsigned_small: u8,
+  unsigned_medium: u16,
+  unsigned_int: u32,
+  unsigned_big: u64,
+  single: f32,
+  double: f64,
+  name: String,
+  labels: Vec<String>,
+  created_at: DateTime<Utc>,
+  started_at: NaiveDateTime,
+  day: NaiveDate,
+  note: Option<String>,
+  reviewers: Option<Vec<String>>,
+}
+
+#[test]
+fn fields_are_mapped_to_arrow_types() {
+  let list = DataType::List(Box::new(Field::new("item", DataType::Utf8, true)));
+  assert_eq!(
+    fields::<TypedRow>(),
+    vec![
+      Field::new("flag", DataType::Boolean, false),
+      Field::new("small", DataType::Int8, false),
+      Field::new("medium", DataType::Int16, false),
+      Field::new("int", DataType::Int32, false),
+      Field::new("big", DataType::Int64, false),
+      Field::new("unsigned_small", DataType::UInt8, false),
+      Field::new("unsigned_medium", DataType::UInt16, false),
+      Field::new("unsigned_int", DataType::UInt32, false),
+      Field::new("unsigned_big", DataType::UInt64, false),
+      Field::new("single", DataType::Float32, false),
+      Field::new("double", DataType::Float64, false),
+      Field::new("name", DataType::Utf8, false),
+      Field::new("labels", list.clone(), false),
+      Field::new("created_at", nanoseconds(), false),
+      Field::new("started_at", nanoseconds(), false),
+      Field::new("day", DataType::Timestamp(TimeUnit::Second, None), false),
+      Field::new("note", DataType::Utf8, true),
+      Field::new("reviewers", list, true),
+    ]
+  );
+  assert_eq!(TypedRow::capabilities(), TableCapabilities::default());
+}
+
+#[derive(Serialize, GrafanaTable)]
+#[allow(dead_code)]
+struct IssueRow {
+  #[table(key)]
+  number: i64,
+  #[table(required)]
+  repository: String,
+  #[table(filter)]
+  state: String,
+  #[table(filter(like))]
+  title: String,
+  #[table(required, filter(gt_eq, lt))]
+  team: String,
+  #[table(filter(eq, not_eq, lt, lt_eq, gt, gt_eq), sortable)]
+  comments: i64,
+  #[table(time, sortable)]
+  created_at: DateTime<Utc>,
+  #[table(watermark)]
+  updated_at: NaiveDateTime,
+  #[table(skip)]
+  body: String,
+}
+
+#[test]
+fn attributes_declare_capabilities() {
+  use FilterOperator::*;
+
+  let columns: Vec<String> = fields::<IssueRow>()
+    .iter()
+    .map(|f| f.name().clone())
+    .collect();
+  assert_eq!(
+    columns,
+    vec![
+      "number",
+      "repository",
+      "state",
+      "title",
+      "team",
+      "comments",
+      "created_at",
+      "updated_at"
+    ]
+  );
+
+  assert_eq!(
+    IssueRow::capabilities(),
+    TableCapabilities {
+      filters: vec![
+        FilterCapability::required("repository"),
+        FilterCapability::eq("state"),
+        FilterCapability {
+          column: "title".to_owned(),
+          operators: vec![Like],
+          required: false,
+        },
+        FilterCapability {
+          column: "team".to_owned(),
+          operators: vec![Eq, GtEq, Lt],
+          required: true,
+        },
+        FilterCapability {
+          column: "comments".to_owned(),
+          operators: vec![Eq, NotEq, Lt, LtEq, Gt, GtEq],
+          required: false,
+        },
+      ],
+      sortable: vec!["comments".to_owned(), "created_at".to_owned()],
+      limit: false,
+      projection: false,
+      time_column: Some("created_at".to_owned()),
+      incremental: Some(IncrementalCapability {
+        key: vec!["number".to_owned()],
+        watermark: "updated_at".to_owned(),
+      }),
+    }
+  );
+}
+
+#[derive(Serialize, GrafanaTable)]
+#[serde(rename_all = "camelCase")]
+#[allow(dead_code)]
+struct RenamedRow {
+  #[table(filter)]
+  run_id: i64,
+  #[serde(rename = "workflow")]
+  workflow_name: String,
+  #[serde(rename(serialize = "conclusion", deserialize = "result"))]
+  run_conclusion: Option<String>,
+  #[table(time)]
+  created_at: DateTime<Utc>,
+  #[serde(skip)]
+  etag: String,
+  #[serde(skip_serializing)]
+  raw: String,
+  r#type: String,
+}
+
+#[test]
+fn columns_are_named_like_serde_serializes_them() {
+  assert_eq!(
+    fields::<RenamedRow>(),
+    vec![
+      Field::new("runId", DataType::Int64, false),
+      Field::new("workflow", DataType::Utf8, false),
+      Field::new("conclusion", DataType::Utf8, true),
+      Field::new("createdAt", nanoseconds(), false),
+      Field::new("type", DataType::Utf8, false),
+    ]
+  );
+
+  let capabilities = RenamedRow::capabilities();
+  assert_eq!(capabilities.filters, vec![FilterCapability::eq("runId")]);
+  assert_eq!(capabilities.time_column, Some("createdAt".to_owned()));
+}
+
+#[derive(Serialize, GrafanaTable)]
+#[serde(rename_all = "SCREAMING-KEBAB-CASE")]
+#[allow(dead_code)]
+struct KebabRow {
+  head_sha: String,
+  #[serde(rename = "id")]
+  run_id: i64,
+}
+
+#[test]
+fn field_renames_override_the_struct_rule() {
+  let columns: Vec<String> = fields::<KebabRow>()
+    .iter()
+    .map(|f| f.name().clone())
+    .collect();
+  assert_eq!(columns, vec!["HEAD-SHA", "id"]);
+}
+
+#[test]
+fn invalid_tables_fail_to_compile() {
+  let t = trybuild::TestCases::new();
+  t.compile_fail("tests/ui/*.rs");
+}
@@ -0,0 +1,8 @@
+use grafana_plugin_sdk::GrafanaTable;
+
+#[derive(GrafanaTable)]
+enum Row {
+  Issue,
+}
+
+fn main() {}
@@ -0,0 +1,9 @@
+use grafana_plugin_sdk::GrafanaTable;
+
+#[derive(GrafanaTable)]
+struct Row {
+  id: i64,
+  reviewers: Vec<i64>,
+}
+
+fn main() {}
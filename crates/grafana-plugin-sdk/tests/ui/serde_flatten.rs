@@ -0,0 +1,17 @@
+use grafana_plugin_sdk::GrafanaTable;
+use serde::Serialize;
+
+#[derive(Serialize)]
+struct Repository {
+  owner: String,
+  name: String,
+}
+
+#[derive(Serialize, GrafanaTable)]
+struct Row {
+  id: i64,
+  #[serde(flatten)]
+  repository: Repository,
+}
+
+fn main() {}
@@ -0,0 +1,9 @@
+use grafana_plugin_sdk::GrafanaTable;
+
+#[derive(GrafanaTable)]
+struct Row {
+  #[table]
+  id: i64,
+}
+
+fn main() {}
@@ -0,0 +1,9 @@
+use grafana_plugin_sdk::GrafanaTable;
+
+#[derive(GrafanaTable)]
+struct Row {
+  #[table(time)]
+  created_at: String,
+}
+
+fn main() {}
@@ -0,0 +1,6 @@
+use grafana_plugin_sdk::GrafanaTable;
+
+#[derive(GrafanaTable)]
+struct Row(i64, String);
+
+fn main() {}
@@ -0,0 +1,12 @@
+use chrono::{DateTime, Utc};
+use grafana_plugin_sdk::GrafanaTable;
+
+#[derive(GrafanaTable)]
+struct Row {
+  #[table(time)]
+  created_at: DateTime<Utc>,
+  #[table(time)]
+  updated_at: DateTime<Utc>,
+}
+
+fn main() {}
@@ -0,0 +1,14 @@
+use chrono::{DateTime, Utc};
+use grafana_plugin_sdk::GrafanaTable;
+
+#[derive(GrafanaTable)]
+struct Row {
+  #[table(key)]
+  id: i64,
+  #[table(watermark)]
+  created_at: DateTime<Utc>,
+  #[table(watermark)]
+  updated_at: DateTime<Utc>,
+}
+
+fn main() {}
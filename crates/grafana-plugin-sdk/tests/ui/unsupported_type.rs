@@ -0,0 +1,11 @@
+use std::collections::HashMap;
+
+use grafana_plugin_sdk::GrafanaTable;
+
+#[derive(GrafanaTable)]
+struct Row {
+  id: i64,
+  labels: HashMap<String, String>,
+}
+
+fn main() {}
@@ -0,0 +1,11 @@
+use grafana_plugin_sdk::GrafanaTable;
+
+#[derive(GrafanaTable)]
+struct Row {
+  #[table(key)]
+  id: i64,
+  #[table(watermark)]
+  updated_at: i64,
+}
+
+fn main() {}
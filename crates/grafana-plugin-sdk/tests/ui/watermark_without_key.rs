@@ -0,0 +1,11 @@
+use chrono::{DateTime, Utc};
+use grafana_plugin_sdk::GrafanaTable;
+
+#[derive(GrafanaTable)]
+struct Row {
+  id: i64,
+  #[table(watermark)]
+  updated_at: DateTime<Utc>,
+}
+
+fn main() {}